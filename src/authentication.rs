@@ -0,0 +1,344 @@
+use crate::{
+    configuration::{AuthSettings, StaticToken},
+    error_chain_fmt,
+};
+use actix_web::{
+    dev::ServiceRequest,
+    http::{header, StatusCode, Uri},
+    HttpMessage, HttpRequest, HttpResponse, ResponseError,
+};
+use anyhow::Context;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::time::{SystemTime, UNIX_EPOCH};
+use subtle::ConstantTimeEq;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Prefix used to send tokens through the `Sec-WebSocket-Protocol` header.
+pub const PROTOCOL_PREFIX: &str = "bearer.";
+
+#[derive(thiserror::Error)]
+pub enum AuthError {
+    #[error("Missing credentials.")]
+    MissingCredentials,
+    #[error("Invalid credentials.")]
+    InvalidCredentials(#[source] anyhow::Error),
+}
+
+impl std::fmt::Debug for AuthError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for AuthError {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::UNAUTHORIZED
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code())
+            .insert_header((header::WWW_AUTHENTICATE, r#"Bearer realm="ws""#))
+            .body(self.to_string())
+    }
+}
+
+/// Authenticated client.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Identity {
+    pub name: String,
+    /// Roles used to authorize tasks, see `authorization::Policy`.
+    pub roles: Vec<String>,
+}
+
+impl Identity {
+    /// Identity used when authentication is disabled, it has the `anonymous` role.
+    pub fn anonymous() -> Self {
+        Self {
+            name: "anonymous".into(),
+            roles: vec!["anonymous".into()],
+        }
+    }
+}
+
+/// Claims carried by HMAC signed tokens.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TokenClaims {
+    /// Identity of the token owner.
+    pub sub: String,
+    /// Expiration time, in seconds since the unix epoch.
+    pub exp: u64,
+    #[serde(default)]
+    pub roles: Vec<String>,
+}
+
+/// `token` query parameter, moved out of the request uri by `take_query_token`.
+struct QueryToken(String);
+
+/// Moves the `token` query parameter to the request extensions, so it never
+/// reaches the request logs.
+pub fn take_query_token(req: &mut ServiceRequest) {
+    #[derive(Deserialize)]
+    struct TokenQuery {
+        token: String,
+    }
+
+    let mut token = None;
+    let query = req
+        .query_string()
+        .split('&')
+        .filter(|pair| match pair.strip_prefix("token=") {
+            Some(_) => {
+                token = actix_web::web::Query::<TokenQuery>::from_query(pair)
+                    .ok()
+                    .map(|query| query.into_inner().token);
+                false
+            }
+            None => !pair.is_empty(),
+        })
+        .collect::<Vec<_>>()
+        .join("&");
+    let token = match token {
+        Some(token) => token,
+        None => return,
+    };
+
+    let uri = match query.as_str() {
+        "" => req.path().to_string(),
+        query => format!("{}?{}", req.path(), query),
+    };
+    if let Ok(uri) = uri.parse::<Uri>() {
+        req.head_mut().uri = uri;
+        req.extensions_mut().insert(QueryToken(token));
+    }
+}
+
+/// Token found on a handshake request.
+#[derive(Debug)]
+pub struct Credentials {
+    pub token: String,
+    /// `Sec-WebSocket-Protocol` that carried the token, it must be echoed
+    /// back to the client for the handshake to succeed.
+    pub protocol: Option<String>,
+}
+
+impl Credentials {
+    /// Looks for a token on the `Authorization` header, `Sec-WebSocket-Protocol`
+    /// header or `token` query parameter, in that order.
+    ///
+    /// The query parameter is only found once `take_query_token` ran.
+    pub fn from_request(req: &HttpRequest) -> Option<Self> {
+        let headers = req.headers();
+        let from_authorization = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(|token| Self {
+                token: token.trim().into(),
+                protocol: None,
+            });
+
+        let from_protocol = || {
+            headers
+                .get(header::SEC_WEBSOCKET_PROTOCOL)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| {
+                    value
+                        .split(',')
+                        .map(str::trim)
+                        .find(|protocol| protocol.starts_with(PROTOCOL_PREFIX))
+                })
+                .map(|protocol| Self {
+                    token: protocol[PROTOCOL_PREFIX.len()..].into(),
+                    protocol: Some(protocol.into()),
+                })
+        };
+
+        let from_query = || {
+            req.extensions()
+                .get::<QueryToken>()
+                .map(|QueryToken(token)| Self {
+                    token: token.clone(),
+                    protocol: None,
+                })
+        };
+
+        from_authorization
+            .or_else(from_protocol)
+            .or_else(from_query)
+            .filter(|credentials| !credentials.token.is_empty())
+    }
+}
+
+/// Validates handshake tokens using the configured `AuthSettings`.
+#[derive(Clone)]
+pub struct Authenticator {
+    settings: AuthSettings,
+}
+
+impl Authenticator {
+    pub fn new(settings: AuthSettings) -> Self {
+        Self { settings }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.settings.enabled
+    }
+
+    #[tracing::instrument(name = "Validate credentials", skip(self, credentials))]
+    pub fn validate(&self, credentials: &Credentials) -> Result<Identity, AuthError> {
+        if let Some(identity) = self.validate_static_token(&credentials.token) {
+            return Ok(identity);
+        }
+
+        match &self.settings.hmac_secret {
+            Some(secret) => validate_signed_token(secret, &credentials.token)
+                .map_err(AuthError::InvalidCredentials),
+            None => Err(AuthError::InvalidCredentials(anyhow::anyhow!(
+                "Unknown static token."
+            ))),
+        }
+    }
+
+    fn validate_static_token(&self, token: &str) -> Option<Identity> {
+        self.settings
+            .tokens
+            .iter()
+            .find(|StaticToken { token: known, .. }| {
+                bool::from(known.as_bytes().ct_eq(token.as_bytes()))
+            })
+            .map(|static_token| Identity {
+                name: static_token.identity.clone(),
+                roles: static_token.roles.clone(),
+            })
+    }
+}
+
+/// Creates a token for `claims`, in the form `<payload>.<signature>` (both
+/// base64url encoded).
+pub fn sign_token(secret: &str, claims: &TokenClaims) -> Result<String, anyhow::Error> {
+    let payload = serde_json::to_vec(claims).context("Failed to serialize token claims.")?;
+    let payload = base64::encode_config(payload, base64::URL_SAFE_NO_PAD);
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).context("Invalid HMAC secret.")?;
+    mac.update(payload.as_bytes());
+    let signature = base64::encode_config(mac.finalize().into_bytes(), base64::URL_SAFE_NO_PAD);
+    Ok(format!("{}.{}", payload, signature))
+}
+
+fn validate_signed_token(secret: &str, token: &str) -> Result<Identity, anyhow::Error> {
+    let (payload, signature) = token
+        .split_once('.')
+        .context("Token is not an HMAC signed token.")?;
+    let signature = base64::decode_config(signature, base64::URL_SAFE_NO_PAD)
+        .context("Failed to decode token signature.")?;
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).context("Invalid HMAC secret.")?;
+    mac.update(payload.as_bytes());
+    mac.verify_slice(&signature)
+        .context("Invalid token signature.")?;
+
+    let payload = base64::decode_config(payload, base64::URL_SAFE_NO_PAD)
+        .context("Failed to decode token payload.")?;
+    let claims =
+        serde_json::from_slice::<TokenClaims>(&payload).context("Failed to parse token claims.")?;
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .context("System time is before the unix epoch.")?
+        .as_secs();
+    if claims.exp <= now {
+        anyhow::bail!("Token expired.");
+    }
+
+    Ok(Identity {
+        name: claims.sub,
+        roles: claims.roles,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn authenticator() -> Authenticator {
+        Authenticator::new(AuthSettings {
+            enabled: true,
+            tokens: vec![StaticToken {
+                token: "static-token".into(),
+                identity: "alice".into(),
+                roles: vec!["admin".into()],
+            }],
+            hmac_secret: Some("secret".into()),
+        })
+    }
+
+    fn credentials(token: &str) -> Credentials {
+        Credentials {
+            token: token.into(),
+            protocol: None,
+        }
+    }
+
+    fn now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    }
+
+    #[test]
+    fn static_token_is_accepted() {
+        let identity = authenticator()
+            .validate(&credentials("static-token"))
+            .unwrap();
+        assert_eq!("alice", identity.name);
+        assert_eq!(vec!["admin".to_string()], identity.roles);
+    }
+
+    #[test]
+    fn signed_token_is_accepted() {
+        let claims = TokenClaims {
+            sub: "bob".into(),
+            exp: now() + 60,
+            roles: vec!["intern".into()],
+        };
+        let token = sign_token("secret", &claims).unwrap();
+        let identity = authenticator().validate(&credentials(&token)).unwrap();
+        assert_eq!("bob", identity.name);
+        assert_eq!(vec!["intern".to_string()], identity.roles);
+    }
+
+    #[test]
+    fn query_token_is_moved_out_of_the_uri() {
+        let mut req =
+            actix_web::test::TestRequest::with_uri("/ws/?a=1&token=abc%3D&b=2").to_srv_request();
+        take_query_token(&mut req);
+        assert_eq!("/ws/?a=1&b=2", req.uri().to_string());
+        let (req, _) = req.into_parts();
+        let credentials = Credentials::from_request(&req).unwrap();
+        assert_eq!("abc=", credentials.token);
+    }
+
+    #[test]
+    fn expired_token_is_rejected() {
+        let claims = TokenClaims {
+            sub: "bob".into(),
+            exp: now() - 1,
+            roles: vec![],
+        };
+        let token = sign_token("secret", &claims).unwrap();
+        assert!(authenticator().validate(&credentials(&token)).is_err());
+    }
+
+    #[test]
+    fn token_with_wrong_signature_is_rejected() {
+        let claims = TokenClaims {
+            sub: "bob".into(),
+            exp: now() + 60,
+            roles: vec![],
+        };
+        let token = sign_token("another-secret", &claims).unwrap();
+        assert!(authenticator().validate(&credentials(&token)).is_err());
+        assert!(authenticator().validate(&credentials("unknown")).is_err());
+    }
+}
@@ -0,0 +1,113 @@
+use crate::{authentication::Identity, configuration::PolicySettings};
+use std::collections::HashMap;
+
+/// `system.task` pattern, where any part can be `*`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct TaskPattern {
+    system: String,
+    task: String,
+}
+
+impl TaskPattern {
+    fn parse(pattern: &str) -> Self {
+        let (system, task) = pattern.split_once('.').unwrap_or((pattern, "*"));
+        Self {
+            system: system.trim().into(),
+            task: task.trim().into(),
+        }
+    }
+
+    fn matches(&self, system: &str, task: &str) -> bool {
+        (self.system == "*" || self.system == system) && (self.task == "*" || self.task == task)
+    }
+}
+
+/// Decides which tasks an identity may run, based on its roles.
+#[derive(Debug, Clone)]
+pub struct Policy {
+    enabled: bool,
+    roles: HashMap<String, Vec<TaskPattern>>,
+}
+
+impl Policy {
+    pub fn new(settings: PolicySettings) -> Self {
+        let roles = settings
+            .roles
+            .into_iter()
+            .map(|(role, patterns)| {
+                let patterns = patterns.iter().map(|o| TaskPattern::parse(o)).collect();
+                // Configuration keys are case insensitive
+                (role.to_lowercase(), patterns)
+            })
+            .collect();
+        Self {
+            enabled: settings.enabled,
+            roles,
+        }
+    }
+
+    pub fn is_allowed(&self, identity: &Identity, system: &str, task: &str) -> bool {
+        if !self.enabled {
+            return true;
+        }
+        identity
+            .roles
+            .iter()
+            .filter_map(|role| self.roles.get(&role.to_lowercase()))
+            .flatten()
+            .any(|pattern| pattern.matches(system, task))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> Policy {
+        let roles = vec![
+            ("admin".to_string(), vec!["*".to_string()]),
+            (
+                "intern".to_string(),
+                vec!["pc_usage.cpu_load".to_string(), "meta.*".to_string()],
+            ),
+        ];
+        Policy::new(PolicySettings {
+            enabled: true,
+            roles: roles.into_iter().collect(),
+        })
+    }
+
+    fn identity(roles: &[&str]) -> Identity {
+        Identity {
+            name: "someone".into(),
+            roles: roles.iter().map(|o| o.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn patterns_are_matched_by_system_and_task() {
+        let policy = policy();
+        let intern = identity(&["intern"]);
+        assert!(policy.is_allowed(&intern, "pc_usage", "cpu_load"));
+        assert!(policy.is_allowed(&intern, "meta", "list_systems"));
+        assert!(!policy.is_allowed(&intern, "pc_usage", "subscribe"));
+        assert!(!policy.is_allowed(&intern, "python_repo", "get_files"));
+    }
+
+    #[test]
+    fn roles_are_combined() {
+        let policy = policy();
+        assert!(policy.is_allowed(&identity(&["intern", "Admin"]), "python_repo", "get_files"));
+        assert!(!policy.is_allowed(&identity(&["unknown"]), "meta", "list_systems"));
+        assert!(!policy.is_allowed(&identity(&[]), "meta", "list_systems"));
+    }
+
+    #[test]
+    fn disabled_policy_allows_everything() {
+        let policy = Policy::new(PolicySettings {
+            enabled: false,
+            roles: Default::default(),
+        });
+        assert!(policy.is_allowed(&identity(&[]), "python_repo", "get_files"));
+    }
+}
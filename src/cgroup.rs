@@ -0,0 +1,375 @@
+//! Minimal readers for the cgroup (v2, with v1 fallback) of the current process.
+use anyhow::Context;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// cgroup v1 reports "no limit" as a huge number instead of `max`.
+const V1_UNLIMITED: u64 = 1 << 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CgroupVersion {
+    V1,
+    V2,
+}
+
+/// Resource usage and limits of a cgroup, values are `null` when the
+/// controller is not available.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct CgroupUsage {
+    pub version: CgroupVersion,
+    /// Path of the cgroup in its hierarchy, the `memory` one for v1.
+    pub path: String,
+    pub cpu: CgroupCpu,
+    pub memory: CgroupMemory,
+    pub pids: CgroupPids,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct CgroupCpu {
+    /// Number of cpus the cgroup can use, `null` when unlimited.
+    pub quota: Option<f64>,
+    /// In microseconds
+    pub period: Option<u64>,
+    /// Cpu time used, in microseconds.
+    pub usage: Option<u64>,
+    /// Number of periods the cgroup was throttled.
+    pub nr_throttled: Option<u64>,
+    /// Time spent throttled, in microseconds.
+    pub throttled: Option<u64>,
+}
+
+/// Sizes are in bytes.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct CgroupMemory {
+    pub usage: Option<u64>,
+    /// `null` when unlimited.
+    pub limit: Option<u64>,
+    /// Times the limit was reached, only reported by v2.
+    pub oom_events: Option<u64>,
+    /// Processes killed by the OOM killer.
+    pub oom_kills: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct CgroupPids {
+    pub current: Option<u64>,
+    /// `null` when unlimited.
+    pub limit: Option<u64>,
+}
+
+/// Paths of the current process, from `/proc/self/cgroup`.
+#[derive(Debug, Default, PartialEq)]
+struct SelfCgroup {
+    /// Path in the v2 hierarchy.
+    unified: Option<String>,
+    /// Path in each v1 hierarchy, by controller.
+    controllers: HashMap<String, String>,
+}
+
+/// Version of the cgroup filesystem mounted at `root`, if any.
+pub fn version(root: &Path) -> Option<CgroupVersion> {
+    if root.join("cgroup.controllers").exists() {
+        Some(CgroupVersion::V2)
+    } else if ["memory", "cpu", "cpuacct", "pids"]
+        .iter()
+        .any(|o| root.join(o).is_dir())
+    {
+        Some(CgroupVersion::V1)
+    } else {
+        None
+    }
+}
+
+/// Content of `/proc/self/cgroup`.
+pub fn self_cgroup(proc_root: &Path) -> Result<String, anyhow::Error> {
+    fs::read_to_string(proc_root.join("self/cgroup")).context("Failed to read process cgroup.")
+}
+
+/// Usage of the cgroup described by `self_cgroup` (see `self_cgroup()`), in the
+/// cgroup filesystem mounted at `root`.
+pub fn read_usage(root: &Path, self_cgroup: &str) -> Result<CgroupUsage, anyhow::Error> {
+    let version = version(root)
+        .with_context(|| format!("No cgroup filesystem mounted at {}.", root.display()))?;
+    let paths = parse_self_cgroup(self_cgroup);
+    let usage = match version {
+        CgroupVersion::V2 => {
+            let path = paths.unified.unwrap_or_else(|| "/".into());
+            let dir = cgroup_dir(root, &path);
+            CgroupUsage {
+                version,
+                path,
+                cpu: read_cpu_v2(&dir),
+                memory: read_memory_v2(&dir),
+                pids: read_pids(&dir),
+            }
+        }
+        CgroupVersion::V1 => {
+            let controller_dir = |controller: &str, mounts: &[&str]| {
+                let path = paths
+                    .controllers
+                    .get(controller)
+                    .map(String::as_str)
+                    .unwrap_or("/");
+                mounts
+                    .iter()
+                    .map(|mount| root.join(mount))
+                    .find(|mount| mount.is_dir())
+                    .map(|mount| cgroup_dir(&mount, path))
+            };
+            let cpu = controller_dir("cpu", &["cpu,cpuacct", "cpu"]);
+            let cpuacct = controller_dir("cpuacct", &["cpu,cpuacct", "cpuacct"]);
+            let memory = controller_dir("memory", &["memory"]);
+            let pids = controller_dir("pids", &["pids"]);
+            CgroupUsage {
+                version,
+                path: paths
+                    .controllers
+                    .get("memory")
+                    .cloned()
+                    .unwrap_or_else(|| "/".into()),
+                cpu: read_cpu_v1(cpu.as_deref(), cpuacct.as_deref()),
+                memory: memory.as_deref().map(read_memory_v1).unwrap_or_default(),
+                pids: pids.as_deref().map(read_pids).unwrap_or_default(),
+            }
+        }
+    };
+    Ok(usage)
+}
+
+/// Directory of the cgroup at `path`, falling back to `root` when the cgroup
+/// itself is mounted there (e.g. in containers without a cgroup namespace).
+fn cgroup_dir(root: &Path, path: &str) -> PathBuf {
+    let dir = root.join(path.trim_start_matches('/'));
+    if dir.is_dir() {
+        dir
+    } else {
+        root.to_path_buf()
+    }
+}
+
+fn read_cpu_v2(dir: &Path) -> CgroupCpu {
+    let (quota, period) = read(dir, "cpu.max")
+        .and_then(|o| parse_cpu_max(&o))
+        .unwrap_or((None, None));
+    let stat = read(dir, "cpu.stat").unwrap_or_default();
+    CgroupCpu {
+        quota,
+        period,
+        usage: keyed_value(&stat, "usage_usec"),
+        nr_throttled: keyed_value(&stat, "nr_throttled"),
+        throttled: keyed_value(&stat, "throttled_usec"),
+    }
+}
+
+fn read_memory_v2(dir: &Path) -> CgroupMemory {
+    let events = read(dir, "memory.events").unwrap_or_default();
+    CgroupMemory {
+        usage: read_u64(dir, "memory.current"),
+        limit: read(dir, "memory.max").and_then(|o| parse_max(&o)),
+        oom_events: keyed_value(&events, "oom"),
+        oom_kills: keyed_value(&events, "oom_kill"),
+    }
+}
+
+fn read_cpu_v1(cpu: Option<&Path>, cpuacct: Option<&Path>) -> CgroupCpu {
+    let quota = cpu
+        .and_then(|dir| read(dir, "cpu.cfs_quota_us"))
+        .and_then(|o| o.trim().parse::<i64>().ok());
+    let period = cpu.and_then(|dir| read_u64(dir, "cpu.cfs_period_us"));
+    let stat = cpu
+        .and_then(|dir| read(dir, "cpu.stat"))
+        .unwrap_or_default();
+    let quota = match (quota, period) {
+        // -1 means unlimited
+        (Some(quota), Some(period)) if quota > 0 && period > 0 => {
+            Some(quota as f64 / period as f64)
+        }
+        _ => None,
+    };
+    CgroupCpu {
+        quota,
+        period,
+        usage: cpuacct
+            .and_then(|dir| read_u64(dir, "cpuacct.usage"))
+            .map(|o| o / 1000),
+        nr_throttled: keyed_value(&stat, "nr_throttled"),
+        throttled: keyed_value(&stat, "throttled_time").map(|o| o / 1000),
+    }
+}
+
+fn read_memory_v1(dir: &Path) -> CgroupMemory {
+    let oom_control = read(dir, "memory.oom_control").unwrap_or_default();
+    CgroupMemory {
+        usage: read_u64(dir, "memory.usage_in_bytes"),
+        limit: read_u64(dir, "memory.limit_in_bytes").filter(|o| *o < V1_UNLIMITED),
+        oom_events: None,
+        oom_kills: keyed_value(&oom_control, "oom_kill"),
+    }
+}
+
+fn read_pids(dir: &Path) -> CgroupPids {
+    CgroupPids {
+        current: read_u64(dir, "pids.current"),
+        limit: read(dir, "pids.max").and_then(|o| parse_max(&o)),
+    }
+}
+
+fn read(dir: &Path, file: &str) -> Option<String> {
+    fs::read_to_string(dir.join(file)).ok()
+}
+
+fn read_u64(dir: &Path, file: &str) -> Option<u64> {
+    read(dir, file)?.trim().parse().ok()
+}
+
+fn parse_self_cgroup(content: &str) -> SelfCgroup {
+    let mut result = SelfCgroup::default();
+    for line in content.lines() {
+        // hierarchy-ID:controller-list:cgroup-path
+        let mut fields = line.splitn(3, ':');
+        let (id, controllers, path) = match (fields.next(), fields.next(), fields.next()) {
+            (Some(id), Some(controllers), Some(path)) => (id, controllers, path),
+            _ => continue,
+        };
+        if id == "0" && controllers.is_empty() {
+            result.unified = Some(path.to_string());
+            continue;
+        }
+        for controller in controllers.split(',') {
+            result
+                .controllers
+                .insert(controller.to_string(), path.to_string());
+        }
+    }
+    result
+}
+
+/// Parses `cpu.max`, returning the quota (in cpus) and the period.
+fn parse_cpu_max(content: &str) -> Option<(Option<f64>, Option<u64>)> {
+    let mut fields = content.split_whitespace();
+    let quota = fields.next()?;
+    let period = fields.next()?.parse::<u64>().ok()?;
+    let quota = match quota {
+        "max" => None,
+        quota => Some(quota.parse::<u64>().ok()? as f64 / period as f64),
+    };
+    Some((quota, Some(period)))
+}
+
+/// Parses a limit that can be `max`, returning `None` when unlimited.
+fn parse_max(content: &str) -> Option<u64> {
+    match content.trim() {
+        "max" => None,
+        value => value.parse().ok(),
+    }
+}
+
+/// Value of `key` in a flat keyed file, e.g. `cpu.stat`.
+fn keyed_value(content: &str, key: &str) -> Option<u64> {
+    content.lines().find_map(|line| {
+        let (name, value) = line.split_once(' ')?;
+        if name == key {
+            value.trim().parse().ok()
+        } else {
+            None
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures/cgroup")
+            .join(name)
+    }
+
+    #[test]
+    fn parse_self_cgroup_reads_both_hierarchies() {
+        let content = "12:cpu,cpuacct:/docker/abc\n4:memory:/docker/abc\n0::/system.slice\n";
+        let result = parse_self_cgroup(content);
+        assert_eq!(Some("/system.slice".to_string()), result.unified);
+        assert_eq!(
+            Some(&"/docker/abc".to_string()),
+            result.controllers.get("cpu")
+        );
+        assert_eq!(
+            Some(&"/docker/abc".to_string()),
+            result.controllers.get("cpuacct")
+        );
+    }
+
+    #[test]
+    fn parse_limits() {
+        assert_eq!(
+            Some((Some(1.5), Some(100000))),
+            parse_cpu_max("150000 100000\n")
+        );
+        assert_eq!(Some((None, Some(100000))), parse_cpu_max("max 100000\n"));
+        assert_eq!(None, parse_max("max\n"));
+        assert_eq!(Some(64), parse_max("64\n"));
+    }
+
+    #[test]
+    fn read_usage_from_v2_fixture() {
+        // The fixture is the cgroup itself, as mounted in a container
+        let usage = read_usage(&fixture("v2"), "0::/docker/abc\n").unwrap();
+        let expected = CgroupUsage {
+            version: CgroupVersion::V2,
+            path: "/docker/abc".into(),
+            cpu: CgroupCpu {
+                quota: Some(2.0),
+                period: Some(100000),
+                usage: Some(5000000),
+                nr_throttled: Some(3),
+                throttled: Some(1500),
+            },
+            memory: CgroupMemory {
+                usage: Some(104857600),
+                limit: Some(536870912),
+                oom_events: Some(2),
+                oom_kills: Some(1),
+            },
+            pids: CgroupPids {
+                current: Some(12),
+                limit: None,
+            },
+        };
+        assert_eq!(expected, usage);
+    }
+
+    #[test]
+    fn read_usage_from_v1_fixture() {
+        let usage = read_usage(&fixture("v1"), "4:memory:/\n2:cpu,cpuacct:/\n").unwrap();
+        let expected = CgroupUsage {
+            version: CgroupVersion::V1,
+            path: "/".into(),
+            cpu: CgroupCpu {
+                quota: Some(0.5),
+                period: Some(100000),
+                usage: Some(2000000),
+                nr_throttled: Some(7),
+                throttled: Some(3000),
+            },
+            memory: CgroupMemory {
+                usage: Some(209715200),
+                limit: None,
+                oom_events: None,
+                oom_kills: Some(4),
+            },
+            pids: CgroupPids {
+                current: Some(5),
+                limit: Some(100),
+            },
+        };
+        assert_eq!(expected, usage);
+    }
+}
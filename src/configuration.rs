@@ -1,7 +1,9 @@
 use serde::Deserialize;
 use serde_with::{serde_as, DurationMilliSeconds};
 use std::{
+    collections::HashMap,
     convert::{TryFrom, TryInto},
+    path::PathBuf,
     time::Duration,
 };
 
@@ -10,6 +12,9 @@ pub struct Settings {
     pub host: String,
     pub port: u16,
     pub websocket: WebsocketSettings,
+    pub auth: AuthSettings,
+    pub policy: PolicySettings,
+    pub pc_usage: PcUsageSettings,
 }
 
 #[serde_as]
@@ -23,6 +28,91 @@ pub struct WebsocketSettings {
     pub client_timeout: Duration,
 }
 
+#[serde_as]
+#[derive(Debug, Clone, Deserialize)]
+pub struct PcUsageSettings {
+    /// Number of samples kept by the background sampler for `history`, 0 keeps none
+    pub history_size: usize,
+    /// In milliseconds
+    #[serde_as(as = "DurationMilliSeconds<u64>")]
+    pub sample_interval: Duration,
+    /// Mount point of the cgroup filesystem read by `cgroup`
+    pub cgroup_root: PathBuf,
+    /// Mount point of procfs, read by the process and host tasks
+    pub procfs_root: PathBuf,
+    /// Mount point of sysfs, read by `cpu` for the frequency of each core
+    pub sysfs_root: PathBuf,
+}
+
+#[derive(Clone, Deserialize)]
+pub struct AuthSettings {
+    /// When disabled every client is accepted as `anonymous`
+    pub enabled: bool,
+    #[serde(default)]
+    pub tokens: Vec<StaticToken>,
+    /// Secret used to verify HMAC signed tokens, signed tokens are rejected if missing
+    #[serde(default)]
+    pub hmac_secret: Option<String>,
+}
+
+impl AuthSettings {
+    /// Fails when authentication is enabled without any way to accept a client.
+    pub fn validate(&self) -> Result<(), String> {
+        let has_secret = self
+            .hmac_secret
+            .as_deref()
+            .is_some_and(|secret| !secret.is_empty());
+        if self.enabled && self.tokens.is_empty() && !has_secret {
+            return Err(
+                "Authentication is enabled but neither `auth.tokens` nor `auth.hmac_secret` \
+                 are configured."
+                    .into(),
+            );
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for AuthSettings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AuthSettings")
+            .field("enabled", &self.enabled)
+            .field("tokens", &self.tokens)
+            .field(
+                "hmac_secret",
+                &self.hmac_secret.as_ref().map(|_| "[REDACTED]"),
+            )
+            .finish()
+    }
+}
+
+#[derive(Clone, Deserialize)]
+pub struct StaticToken {
+    pub token: String,
+    pub identity: String,
+    #[serde(default)]
+    pub roles: Vec<String>,
+}
+
+impl std::fmt::Debug for StaticToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StaticToken")
+            .field("token", &"[REDACTED]")
+            .field("identity", &self.identity)
+            .field("roles", &self.roles)
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PolicySettings {
+    /// When disabled every identity may run every task
+    pub enabled: bool,
+    /// Allowed `system.task` patterns for each role, `*` matches any system or task
+    #[serde(default)]
+    pub roles: HashMap<String, Vec<String>>,
+}
+
 pub fn get_configuration() -> Result<Settings, config::ConfigError> {
     let mut settings = config::Config::default();
     let base_path = std::env::current_dir().expect("Failed to determine the current directory.");
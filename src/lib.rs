@@ -1,4 +1,9 @@
+pub mod authentication;
+pub mod authorization;
+pub mod cgroup;
 pub mod configuration;
+pub mod metrics;
+pub mod procfs;
 pub mod startup;
 pub mod telemetry;
 pub mod websocket;
@@ -8,10 +13,15 @@ fn error_chain_fmt(
     f: &mut std::fmt::Formatter<'_>,
 ) -> std::fmt::Result {
     writeln!(f, "{}\n", e)?;
-    let mut current = e.source();
-    while let Some(cause) = current {
+    for cause in error_causes(e) {
         writeln!(f, "Caused by:\n\t{}", cause)?;
-        current = cause.source();
     }
     Ok(())
 }
+
+/// Iterates over the chain of sources of an error.
+fn error_causes(
+    e: &impl std::error::Error,
+) -> impl Iterator<Item = &(dyn std::error::Error + 'static)> {
+    std::iter::successors(e.source(), |cause| cause.source())
+}
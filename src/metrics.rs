@@ -0,0 +1,197 @@
+//! Prometheus metrics exposed on `/metrics`.
+use crate::websocket::pc_usage::{GetHostMetrics, HostMetrics};
+use actix::Recipient;
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use prometheus::{
+    Encoder, Gauge, GaugeVec, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, Opts, Registry,
+    TextEncoder,
+};
+use std::time::Duration;
+
+/// Label used for systems and tasks that are not registered, so clients can't
+/// create arbitrary series.
+pub const UNKNOWN_LABEL: &str = "unknown";
+
+/// Label used for the messages sent by the websocket route itself.
+pub const WEBSOCKET_LABEL: &str = "websocket";
+
+/// Metrics of the websocket server, shared by every session.
+pub struct ServerMetrics {
+    registry: Registry,
+    sessions: IntGauge,
+    tasks: IntCounterVec,
+    task_errors: IntCounterVec,
+    task_duration: HistogramVec,
+    messages_sent: IntCounterVec,
+}
+
+impl ServerMetrics {
+    pub fn new() -> Result<Self, prometheus::Error> {
+        let registry = Registry::new();
+        let sessions = IntGauge::new("websocket_sessions", "Open websocket sessions.")?;
+        let tasks = IntCounterVec::new(
+            Opts::new("websocket_tasks_total", "Tasks dispatched to a subsystem."),
+            &["system", "task"],
+        )?;
+        let task_errors = IntCounterVec::new(
+            Opts::new(
+                "websocket_task_errors_total",
+                "Tasks answered with an error.",
+            ),
+            &["system", "task"],
+        )?;
+        let task_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "websocket_task_duration_seconds",
+                "Time until the first reply to a task.",
+            ),
+            &["system", "task"],
+        )?;
+        let messages_sent = IntCounterVec::new(
+            Opts::new("websocket_messages_sent_total", "Messages sent to clients."),
+            &["system"],
+        )?;
+        registry.register(Box::new(sessions.clone()))?;
+        registry.register(Box::new(tasks.clone()))?;
+        registry.register(Box::new(task_errors.clone()))?;
+        registry.register(Box::new(task_duration.clone()))?;
+        registry.register(Box::new(messages_sent.clone()))?;
+        Ok(Self {
+            registry,
+            sessions,
+            tasks,
+            task_errors,
+            task_duration,
+            messages_sent,
+        })
+    }
+
+    pub fn session_opened(&self) {
+        self.sessions.inc();
+    }
+
+    pub fn session_closed(&self) {
+        self.sessions.dec();
+    }
+
+    pub fn task_dispatched(&self, system: &str, task: &str) {
+        self.tasks.with_label_values(&[system, task]).inc();
+    }
+
+    pub fn task_failed(&self, system: &str, task: &str) {
+        self.task_errors.with_label_values(&[system, task]).inc();
+    }
+
+    pub fn task_answered(&self, system: &str, task: &str, elapsed: Duration) {
+        self.task_duration
+            .with_label_values(&[system, task])
+            .observe(elapsed.as_secs_f64());
+    }
+
+    pub fn message_sent(&self, system: &str) {
+        self.messages_sent.with_label_values(&[system]).inc();
+    }
+
+    /// Server metrics, followed by `host` ones, in the Prometheus text format.
+    pub fn render(&self, host: Option<&HostMetrics>) -> Result<String, anyhow::Error> {
+        let mut families = self.registry.gather();
+        if let Some(host) = host {
+            let host = host_registry(host).context("Failed to register host metrics.")?;
+            families.extend(host.gather());
+        }
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&families, &mut buffer)
+            .context("Failed to encode metrics.")?;
+        String::from_utf8(buffer).context("Metrics are not valid UTF-8.")
+    }
+}
+
+/// Registry holding the current values of the host metrics.
+fn host_registry(host: &HostMetrics) -> Result<Registry, prometheus::Error> {
+    let registry = Registry::new();
+    let gauge = |name: &str, help: &str, value: f64| -> Result<(), prometheus::Error> {
+        let gauge = Gauge::new(name, help)?;
+        gauge.set(value);
+        registry.register(Box::new(gauge))
+    };
+    if let Some(sample) = &host.sample {
+        gauge(
+            "host_cpu_usage_ratio",
+            "Fraction of time the cpus were busy, across all cores.",
+            sample.cpu as f64,
+        )?;
+        gauge(
+            "host_cpu_user_ratio",
+            "Fraction of time the cpus spent on user mode, across all cores.",
+            sample.cpu_user as f64,
+        )?;
+    }
+    let memory = &host.memory;
+    gauge(
+        "host_memory_total_bytes",
+        "Total memory.",
+        memory.memory.total as f64,
+    )?;
+    gauge(
+        "host_memory_used_bytes",
+        "Memory not available for new processes.",
+        memory.memory.used() as f64,
+    )?;
+    gauge(
+        "host_swap_total_bytes",
+        "Total swap.",
+        memory.swap.total as f64,
+    )?;
+    gauge(
+        "host_swap_used_bytes",
+        "Used swap.",
+        memory.swap.used as f64,
+    )?;
+    gauge(
+        "host_uptime_seconds",
+        "Time since the host booted.",
+        host.uptime.as_secs_f64(),
+    )?;
+    let load_average = GaugeVec::new(
+        Opts::new("host_load_average", "System load average."),
+        &["period"],
+    )?;
+    let periods = [
+        ("1m", host.load_average.one),
+        ("5m", host.load_average.five),
+        ("15m", host.load_average.fifteen),
+    ];
+    for (period, value) in periods {
+        load_average.with_label_values(&[period]).set(value as f64);
+    }
+    registry.register(Box::new(load_average))?;
+    Ok(registry)
+}
+
+#[tracing::instrument(name = "Scraping metrics", skip(server_metrics, host_metrics))]
+pub async fn metrics_endpoint(
+    server_metrics: web::Data<ServerMetrics>,
+    host_metrics: web::Data<Recipient<GetHostMetrics>>,
+) -> Result<HttpResponse, actix_web::Error> {
+    // Server metrics are still useful without the host ones
+    let host = match host_metrics.send(GetHostMetrics).await {
+        Ok(Ok(host)) => Some(host),
+        Ok(Err(e)) => {
+            tracing::error!("Failed to read host metrics: {:?}", e);
+            None
+        }
+        Err(e) => {
+            tracing::error!("Failed to reach PcUsageSystem: {:?}", e);
+            None
+        }
+    };
+    let body = server_metrics.render(host.as_ref()).map_err(|e| {
+        tracing::error!("{:?}", e);
+        actix_web::error::ErrorInternalServerError("Failed to render metrics.")
+    })?;
+    Ok(HttpResponse::Ok()
+        .content_type(prometheus::TEXT_FORMAT)
+        .body(body))
+}
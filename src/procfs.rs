@@ -0,0 +1,431 @@
+//! Minimal readers for the Linux `/proc` filesystem.
+use anyhow::Context;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Snapshot of a process, read from `/proc/<pid>/{stat,status,cmdline}`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Process {
+    pub pid: u32,
+    pub name: String,
+    pub cmdline: String,
+    pub state: char,
+    pub uid: Option<u32>,
+    /// Time spent on user and kernel mode, in clock ticks.
+    pub cpu_ticks: u64,
+    /// Resident memory, in bytes.
+    pub rss: u64,
+    pub threads: u64,
+}
+
+/// Bytes read and written by a process, from `/proc/<pid>/io`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ProcessIo {
+    pub read_bytes: u64,
+    pub write_bytes: u64,
+}
+
+/// Cumulative IO counters of a block device, from `/proc/diskstats`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiskStats {
+    pub name: String,
+    pub reads: u64,
+    pub read_bytes: u64,
+    pub writes: u64,
+    pub written_bytes: u64,
+    /// Time spent doing IO, in milliseconds.
+    pub io_time: u64,
+}
+
+/// Pressure stall information of a resource, from `/proc/pressure/<resource>`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Pressure {
+    /// Time at least one task was stalled on the resource.
+    pub some: PressureStall,
+    /// Time all non-idle tasks were stalled at once, `null` when not reported
+    /// (cpu on kernels before 5.13).
+    pub full: Option<PressureStall>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct PressureStall {
+    /// Percentage of the last 10 seconds spent stalled.
+    pub avg10: f32,
+    /// Percentage of the last 60 seconds spent stalled.
+    pub avg60: f32,
+    /// Percentage of the last 300 seconds spent stalled.
+    pub avg300: f32,
+    /// Total stall time, in microseconds.
+    pub total: u64,
+}
+
+/// Fields of `/proc/<pid>/stat` used by `Process`.
+#[derive(Debug, PartialEq)]
+struct Stat {
+    name: String,
+    state: char,
+    cpu_ticks: u64,
+    threads: u64,
+}
+
+/// Fields of `/proc/<pid>/status` used by `Process`.
+#[derive(Debug, Default, PartialEq)]
+struct Status {
+    uid: Option<u32>,
+    rss: u64,
+}
+
+/// Pids of the processes currently running.
+pub fn pids(root: &Path) -> Result<Vec<u32>, anyhow::Error> {
+    let entries =
+        fs::read_dir(root).with_context(|| format!("Failed to read {}.", root.display()))?;
+    let pids = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| entry.file_name().to_str()?.parse().ok())
+        .collect();
+    Ok(pids)
+}
+
+fn process_path(root: &Path, pid: u32) -> PathBuf {
+    root.join(pid.to_string())
+}
+
+/// Whether a process with `pid` is currently running.
+pub fn process_exists(root: &Path, pid: u32) -> bool {
+    process_path(root, pid).join("stat").exists()
+}
+
+pub fn read_process(root: &Path, pid: u32) -> Result<Process, anyhow::Error> {
+    let path = process_path(root, pid);
+    let stat = fs::read_to_string(path.join("stat"))
+        .with_context(|| format!("Failed to read stat of process {}.", pid))?;
+    let stat = parse_stat(&stat).with_context(|| format!("Invalid stat of process {}.", pid))?;
+    let status = fs::read_to_string(path.join("status"))
+        .map(|o| parse_status(&o))
+        .unwrap_or_default();
+    // Kernel threads have an empty cmdline
+    let cmdline = fs::read(path.join("cmdline"))
+        .map(|o| parse_cmdline(&o))
+        .unwrap_or_default();
+    Ok(Process {
+        pid,
+        name: stat.name,
+        cmdline,
+        state: stat.state,
+        uid: status.uid,
+        cpu_ticks: stat.cpu_ticks,
+        rss: status.rss,
+        threads: stat.threads,
+    })
+}
+
+/// Reads every running process, skipping the ones that exit while being read.
+pub fn read_processes(root: &Path) -> Result<Vec<Process>, anyhow::Error> {
+    let processes = pids(root)?
+        .into_iter()
+        .filter_map(|pid| read_process(root, pid).ok())
+        .collect();
+    Ok(processes)
+}
+
+/// Number of file descriptors opened by a process, `None` if not allowed to read them.
+pub fn open_fds(root: &Path, pid: u32) -> Option<usize> {
+    fs::read_dir(process_path(root, pid).join("fd"))
+        .ok()
+        .map(|entries| entries.count())
+}
+
+/// IO counters of a process, `None` if not allowed to read them.
+pub fn process_io(root: &Path, pid: u32) -> Option<ProcessIo> {
+    fs::read_to_string(process_path(root, pid).join("io"))
+        .ok()
+        .map(|o| parse_io(&o))
+}
+
+/// Clock ticks spent by all cpus, along with the number of cpus, from `/proc/stat`.
+pub fn cpu_ticks(root: &Path) -> Result<(u64, usize), anyhow::Error> {
+    let stat = fs::read_to_string(root.join("stat")).context("Failed to read cpu stats.")?;
+    parse_cpu_ticks(&stat).context("Invalid cpu stats.")
+}
+
+/// Host name, from `/proc/sys/kernel/hostname`.
+pub fn hostname(root: &Path) -> Result<String, anyhow::Error> {
+    read_trimmed(&root.join("sys/kernel/hostname")).context("Failed to read hostname.")
+}
+
+/// Kernel release, from `/proc/sys/kernel/osrelease`.
+pub fn kernel_version(root: &Path) -> Result<String, anyhow::Error> {
+    read_trimmed(&root.join("sys/kernel/osrelease")).context("Failed to read kernel version.")
+}
+
+/// IO counters of every block device that did any IO since boot.
+pub fn disk_stats(root: &Path) -> Result<Vec<DiskStats>, anyhow::Error> {
+    let diskstats =
+        fs::read_to_string(root.join("diskstats")).context("Failed to read disk stats.")?;
+    Ok(parse_diskstats(&diskstats))
+}
+
+/// Pressure stall information of `resource` (`cpu`, `memory` or `io`).
+///
+/// Fails with `io::ErrorKind::NotFound` on kernels without PSI and with
+/// `io::ErrorKind::Unsupported` when it was disabled at boot.
+pub fn pressure(root: &Path, resource: &str) -> io::Result<Pressure> {
+    let content = fs::read_to_string(root.join("pressure").join(resource))?;
+    parse_pressure(&content).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Invalid {} pressure.", resource),
+        )
+    })
+}
+
+fn read_trimmed(path: &Path) -> Result<String, std::io::Error> {
+    fs::read_to_string(path).map(|o| o.trim().to_string())
+}
+
+/// User names by uid, from `/etc/passwd`.
+pub fn users() -> HashMap<u32, String> {
+    fs::read_to_string("/etc/passwd")
+        .map(|o| parse_passwd(&o))
+        .unwrap_or_default()
+}
+
+fn parse_stat(stat: &str) -> Option<Stat> {
+    // The name is between parenthesis and can contain spaces or parenthesis
+    let start = stat.find('(')?;
+    let end = stat.rfind(')')?;
+    let name = stat.get(start + 1..end)?.to_string();
+    // Fields after the name, starting from field 3 (state)
+    let fields = stat.get(end + 1..)?.split_whitespace().collect::<Vec<_>>();
+    let field = |n: usize| fields.get(n - 3).and_then(|o| o.parse::<u64>().ok());
+    Some(Stat {
+        name,
+        state: fields.first()?.chars().next()?,
+        cpu_ticks: field(14)? + field(15)?,
+        threads: field(20)?,
+    })
+}
+
+fn parse_status(status: &str) -> Status {
+    let mut result = Status::default();
+    for line in status.lines() {
+        let (key, value) = match line.split_once(':') {
+            Some(o) => o,
+            None => continue,
+        };
+        let value = value.split_whitespace().next();
+        match key {
+            "Uid" => result.uid = value.and_then(|o| o.parse().ok()),
+            "VmRSS" => {
+                result.rss = value.and_then(|o| o.parse::<u64>().ok()).unwrap_or(0) * 1024;
+            }
+            _ => {}
+        }
+    }
+    result
+}
+
+fn parse_io(io: &str) -> ProcessIo {
+    let mut result = ProcessIo::default();
+    for line in io.lines() {
+        let (key, value) = match line.split_once(':') {
+            Some(o) => o,
+            None => continue,
+        };
+        let value = value.trim().parse().unwrap_or(0);
+        match key {
+            "read_bytes" => result.read_bytes = value,
+            "write_bytes" => result.write_bytes = value,
+            _ => {}
+        }
+    }
+    result
+}
+
+fn parse_cmdline(cmdline: &[u8]) -> String {
+    cmdline
+        .split(|o| *o == 0)
+        .filter(|o| !o.is_empty())
+        .map(String::from_utf8_lossy)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn parse_cpu_ticks(stat: &str) -> Option<(u64, usize)> {
+    let total = stat
+        .lines()
+        .find(|line| line.starts_with("cpu "))?
+        .split_whitespace()
+        .skip(1)
+        .filter_map(|o| o.parse::<u64>().ok())
+        .sum();
+    let cpus = stat
+        .lines()
+        .filter(|line| line.starts_with("cpu") && !line.starts_with("cpu "))
+        .count();
+    Some((total, cpus.max(1)))
+}
+
+fn parse_diskstats(diskstats: &str) -> Vec<DiskStats> {
+    // Sectors are always 512 bytes, whatever the device sector size
+    const SECTOR_SIZE: u64 = 512;
+    diskstats
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace().skip(2);
+            let name = fields.next()?.to_string();
+            let values = fields
+                .map(|o| o.parse::<u64>().ok())
+                .collect::<Option<Vec<_>>>()?;
+            // Partitions on old kernels only report 4 fields
+            if values.len() < 10 {
+                return None;
+            }
+            Some(DiskStats {
+                name,
+                reads: values[0],
+                read_bytes: values[2] * SECTOR_SIZE,
+                writes: values[4],
+                written_bytes: values[6] * SECTOR_SIZE,
+                io_time: values[9],
+            })
+        })
+        .filter(|o| o.reads > 0 || o.writes > 0)
+        .collect()
+}
+
+fn parse_pressure(content: &str) -> Option<Pressure> {
+    let stall = |kind: &str| {
+        let line = content.lines().find(|line| line.starts_with(kind))?;
+        let value = |key: &str| {
+            line.split_whitespace()
+                .find_map(|field| field.strip_prefix(key)?.strip_prefix('='))
+        };
+        Some(PressureStall {
+            avg10: value("avg10")?.parse().ok()?,
+            avg60: value("avg60")?.parse().ok()?,
+            avg300: value("avg300")?.parse().ok()?,
+            total: value("total")?.parse().ok()?,
+        })
+    };
+    Some(Pressure {
+        some: stall("some ")?,
+        full: stall("full "),
+    })
+}
+
+fn parse_passwd(passwd: &str) -> HashMap<u32, String> {
+    passwd
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split(':');
+            let name = fields.next()?;
+            let uid = fields.nth(1)?.parse().ok()?;
+            Some((uid, name.to_string()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_stat_handles_names_with_spaces() {
+        let stat = "42 (Web Content (1)) S 1 42 42 0 -1 4194560 100 0 0 0 150 50 0 0 20 0 7 0 1234 1000 200";
+        let expected = Stat {
+            name: "Web Content (1)".into(),
+            state: 'S',
+            cpu_ticks: 200,
+            threads: 7,
+        };
+        assert_eq!(Some(expected), parse_stat(stat));
+        assert_eq!(None, parse_stat("42 (truncated"));
+    }
+
+    #[test]
+    fn parse_status_reads_uid_and_rss() {
+        let status = "Name:\tbash\nUid:\t1000\t1000\t1000\t1000\nVmRSS:\t    2048 kB\n";
+        let expected = Status {
+            uid: Some(1000),
+            rss: 2048 * 1024,
+        };
+        assert_eq!(expected, parse_status(status));
+    }
+
+    #[test]
+    fn parse_io_reads_storage_bytes() {
+        let io = "rchar: 100\nwchar: 50\nread_bytes: 4096\nwrite_bytes: 8192\n";
+        let expected = ProcessIo {
+            read_bytes: 4096,
+            write_bytes: 8192,
+        };
+        assert_eq!(expected, parse_io(io));
+    }
+
+    #[test]
+    fn parse_cmdline_joins_arguments() {
+        assert_eq!(
+            "python -m http.server",
+            parse_cmdline(b"python\0-m\0http.server\0")
+        );
+        assert_eq!("", parse_cmdline(b""));
+    }
+
+    #[test]
+    fn parse_cpu_ticks_sums_all_cpus() {
+        let stat = "cpu  10 0 20 70 0 0 0 0 0 0\ncpu0 5 0 10 35 0 0 0 0 0 0\ncpu1 5 0 10 35 0 0 0 0 0 0\nintr 1\n";
+        assert_eq!(Some((100, 2)), parse_cpu_ticks(stat));
+    }
+
+    #[test]
+    fn parse_diskstats_skips_idle_devices() {
+        let diskstats = "   7       0 loop0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0\n 254       0 vda 9689 5533 3017458 17775 41561 49656 53737744 347543 0 31484 376828 19001 0 42570528 11503 233 6\n   8       1 sda1 10 20 30 40\n";
+        let expected = vec![DiskStats {
+            name: "vda".into(),
+            reads: 9689,
+            read_bytes: 3017458 * 512,
+            writes: 41561,
+            written_bytes: 53737744 * 512,
+            io_time: 31484,
+        }];
+        assert_eq!(expected, parse_diskstats(diskstats));
+    }
+
+    #[test]
+    fn parse_pressure_reads_some_and_full() {
+        let pressure = "some avg10=1.50 avg60=0.75 avg300=0.25 total=123456\nfull avg10=0.50 avg60=0.00 avg300=0.00 total=789\n";
+        let expected = Pressure {
+            some: PressureStall {
+                avg10: 1.5,
+                avg60: 0.75,
+                avg300: 0.25,
+                total: 123456,
+            },
+            full: Some(PressureStall {
+                avg10: 0.5,
+                avg60: 0.0,
+                avg300: 0.0,
+                total: 789,
+            }),
+        };
+        assert_eq!(Some(expected), parse_pressure(pressure));
+        // Older kernels only report `some` for cpu
+        let cpu = parse_pressure("some avg10=0.00 avg60=0.00 avg300=0.00 total=0\n").unwrap();
+        assert_eq!(None, cpu.full);
+        assert_eq!(None, parse_pressure("some avg10=0.00 total=0\n"));
+    }
+
+    #[test]
+    fn parse_passwd_maps_uids() {
+        let users =
+            parse_passwd("root:x:0:0:root:/root:/bin/bash\nbob:x:1000:1000::/home/bob:/bin/sh\n");
+        assert_eq!(Some(&"root".to_string()), users.get(&0));
+        assert_eq!(Some(&"bob".to_string()), users.get(&1000));
+    }
+}
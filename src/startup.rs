@@ -1,54 +1,126 @@
 use crate::{
-    configuration::{Settings, WebsocketSettings},
-    websocket::{pc_usage::PcUsageSystem, python_repo::PythonRepoSystem, route::ws_index},
+    authentication::{take_query_token, Authenticator},
+    authorization::Policy,
+    configuration::{AuthSettings, PolicySettings, Settings, WebsocketSettings},
+    metrics::{metrics_endpoint, ServerMetrics},
+    websocket::{
+        meta::MetaSystem,
+        metrics_source::{MetricsSource, SystemMetrics},
+        pc_usage::{GetHostMetrics, PcUsageSystem},
+        python_repo::PythonRepoSystem,
+        registry::SubSystemRegistry,
+        route::ws_index,
+    },
 };
-use actix::Actor;
+use actix::{Actor, Addr, Recipient};
 use actix_web::{
-    dev::Server,
+    dev::{Server, Service},
     web::{self, Data},
     App, HttpServer,
 };
-use std::net::TcpListener;
+use std::{net::TcpListener, sync::Arc};
 use tracing_actix_web::TracingLogger;
 
 pub struct Application {
     port: u16,
     server: Server,
+    registry: SubSystemRegistry,
 }
 
 impl Application {
     pub async fn build(configuration: Settings) -> Result<Self, std::io::Error> {
+        Self::build_with_metrics(configuration, Arc::new(SystemMetrics)).await
+    }
+
+    /// Same as `build`, reading the host metrics from `metrics`.
+    pub async fn build_with_metrics(
+        configuration: Settings,
+        metrics: Arc<dyn MetricsSource>,
+    ) -> Result<Self, std::io::Error> {
+        configuration
+            .auth
+            .validate()
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
         let address = format!("{}:{}", configuration.host, configuration.port);
         let listener = TcpListener::bind(&address)?;
         let port = listener.local_addr().unwrap().port();
-        let server = run(listener, configuration.websocket)?;
-        Ok(Self { port, server })
+        let pc_usage = PcUsageSystem::new(configuration.pc_usage.clone(), metrics).start();
+        let registry = build_registry(pc_usage.clone());
+        let server = run(
+            listener,
+            configuration.websocket,
+            configuration.auth,
+            configuration.policy,
+            registry.clone(),
+            pc_usage.recipient(),
+        )?;
+        Ok(Self {
+            port,
+            server,
+            registry,
+        })
     }
 
     pub fn port(&self) -> u16 {
         self.port
     }
 
+    pub fn registry(&self) -> &SubSystemRegistry {
+        &self.registry
+    }
+
     pub async fn run_until_stopped(self) -> Result<(), std::io::Error> {
         self.server.await
     }
 }
 
+/// Starts every other subsystem and registers it along with `pc_usage`, new
+/// subsystems should be added here.
+pub fn build_registry(pc_usage: Addr<PcUsageSystem>) -> SubSystemRegistry {
+    let mut registry = SubSystemRegistry::default();
+    registry
+        .register(PythonRepoSystem::default().start())
+        .register(pc_usage);
+    // Registered last so it can describe every other system
+    let meta_system = MetaSystem::new(&registry).start();
+    registry.register(meta_system);
+    registry
+}
+
 pub fn run(
     listener: TcpListener,
     websocket_settings: WebsocketSettings,
+    auth_settings: AuthSettings,
+    policy_settings: PolicySettings,
+    registry: SubSystemRegistry,
+    host_metrics: Recipient<GetHostMetrics>,
 ) -> Result<Server, std::io::Error> {
     tracing::info!("{:?}", websocket_settings);
+    tracing::info!("{:?}", auth_settings);
+    tracing::info!("{:?}", policy_settings);
+    tracing::info!("Registered subsystems: {:?}", registry.names());
     let websocket_settings = Data::new(websocket_settings);
-    let python_repo_server = Data::new(PythonRepoSystem::default().start());
-    let pc_usage_server = Data::new(PcUsageSystem::default().start());
+    let registry = Data::new(registry);
+    let authenticator = Data::new(Authenticator::new(auth_settings));
+    let policy = Data::new(Policy::new(policy_settings));
+    let server_metrics = Data::new(ServerMetrics::new().map_err(std::io::Error::other)?);
+    let host_metrics = Data::new(host_metrics);
     let server = HttpServer::new(move || {
         App::new()
             .wrap(TracingLogger::default())
+            // Wrapped last so the token is gone before the request is logged
+            .wrap_fn(|mut req, srv| {
+                take_query_token(&mut req);
+                srv.call(req)
+            })
             .route("/ws/", web::get().to(ws_index))
+            .route("/metrics", web::get().to(metrics_endpoint))
             .app_data(websocket_settings.clone())
-            .app_data(python_repo_server.clone())
-            .app_data(pc_usage_server.clone())
+            .app_data(registry.clone())
+            .app_data(authenticator.clone())
+            .app_data(policy.clone())
+            .app_data(server_metrics.clone())
+            .app_data(host_metrics.clone())
     })
     .listen(listener)?
     .run();
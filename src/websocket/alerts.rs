@@ -0,0 +1,240 @@
+use super::{history::HistorySample, message::RequestId};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DurationMilliSeconds};
+use std::time::Duration;
+
+/// Metric of a `HistorySample` an alert is evaluated on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertMetric {
+    /// Fraction of time the cpus were busy, across all cores.
+    Cpu,
+    /// Fraction of time the cpus spent on user mode, across all cores.
+    CpuUser,
+    /// In bytes
+    MemoryUsed,
+    /// In bytes
+    MemoryAvailable,
+}
+
+impl AlertMetric {
+    pub fn value(&self, sample: &HistorySample) -> f64 {
+        match self {
+            AlertMetric::Cpu => sample.cpu as f64,
+            AlertMetric::CpuUser => sample.cpu_user as f64,
+            AlertMetric::MemoryUsed => sample.memory_used as f64,
+            AlertMetric::MemoryAvailable => {
+                sample.memory_total.saturating_sub(sample.memory_used) as f64
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertCondition {
+    Above,
+    Below,
+}
+
+/// E.g. `{"metric": "cpu_user", "condition": "above", "threshold": 0.9, "duration": 30000}`.
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct AlertRule {
+    pub metric: AlertMetric,
+    pub condition: AlertCondition,
+    pub threshold: f64,
+    /// How long the condition must hold before firing, in milliseconds (0 by default).
+    #[serde(default)]
+    #[serde_as(as = "DurationMilliSeconds<u64>")]
+    #[schemars(with = "u64")]
+    pub duration: Duration,
+}
+
+impl AlertRule {
+    fn is_met(&self, value: f64) -> bool {
+        match self.condition {
+            AlertCondition::Above => value > self.threshold,
+            AlertCondition::Below => value < self.threshold,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertState {
+    Firing,
+    Resolved,
+}
+
+/// Message pushed to the client when an alert fires or resolves.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct AlertEvent {
+    pub alert_id: u64,
+    pub state: AlertState,
+    /// Value of the metric on the sample that triggered the event.
+    pub value: f64,
+    /// In milliseconds since the unix epoch
+    pub timestamp: u64,
+}
+
+/// Alert registered by a client.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct AlertInfo {
+    pub id: u64,
+    pub rule: AlertRule,
+    pub firing: bool,
+}
+
+/// Alert rule along with its evaluation state.
+#[derive(Debug)]
+pub struct Alert {
+    pub id: u64,
+    pub rule: AlertRule,
+    /// `request_id` used to push events to the client.
+    pub request_id: RequestId,
+    /// Timestamp of the first sample of the current run meeting the condition.
+    pending_since: Option<u64>,
+    firing: bool,
+}
+
+impl Alert {
+    pub fn new(id: u64, rule: AlertRule, request_id: RequestId) -> Self {
+        Self {
+            id,
+            rule,
+            request_id,
+            pending_since: None,
+            firing: false,
+        }
+    }
+
+    pub fn info(&self) -> AlertInfo {
+        AlertInfo {
+            id: self.id,
+            rule: self.rule.clone(),
+            firing: self.firing,
+        }
+    }
+
+    /// Updates the alert with a new sample, returning an event if it fired or resolved.
+    pub fn evaluate(&mut self, sample: &HistorySample) -> Option<AlertEvent> {
+        let value = self.rule.metric.value(sample);
+        let state = if self.rule.is_met(value) {
+            let since = *self.pending_since.get_or_insert(sample.timestamp);
+            let elapsed = Duration::from_millis(sample.timestamp.saturating_sub(since));
+            if self.firing || elapsed < self.rule.duration {
+                return None;
+            }
+            self.firing = true;
+            AlertState::Firing
+        } else {
+            self.pending_since = None;
+            if !self.firing {
+                return None;
+            }
+            self.firing = false;
+            AlertState::Resolved
+        };
+        Some(AlertEvent {
+            alert_id: self.id,
+            state,
+            value,
+            timestamp: sample.timestamp,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(timestamp: u64, cpu_user: f32) -> HistorySample {
+        HistorySample {
+            timestamp,
+            cpu: cpu_user,
+            cpu_user,
+            memory_used: 0,
+            memory_total: 0,
+        }
+    }
+
+    fn states(alert: &mut Alert, samples: &[HistorySample]) -> Vec<Option<AlertState>> {
+        samples
+            .iter()
+            .map(|o| alert.evaluate(o).map(|event| event.state))
+            .collect()
+    }
+
+    #[test]
+    fn alert_fires_after_duration_and_resolves() {
+        let rule = AlertRule {
+            metric: AlertMetric::CpuUser,
+            condition: AlertCondition::Above,
+            threshold: 0.9,
+            duration: Duration::from_millis(1000),
+        };
+        let mut alert = Alert::new(1, rule, RequestId::default());
+        let samples = [
+            sample(0, 0.95),
+            sample(500, 0.95),
+            sample(1000, 0.95),
+            sample(1500, 0.95),
+            sample(2000, 0.5),
+            sample(2500, 0.5),
+        ];
+        let expected = vec![
+            None,
+            None,
+            Some(AlertState::Firing),
+            None,
+            Some(AlertState::Resolved),
+            None,
+        ];
+        assert_eq!(expected, states(&mut alert, &samples));
+    }
+
+    #[test]
+    fn alert_duration_restarts_when_condition_stops_holding() {
+        let rule = AlertRule {
+            metric: AlertMetric::CpuUser,
+            condition: AlertCondition::Above,
+            threshold: 0.9,
+            duration: Duration::from_millis(1000),
+        };
+        let mut alert = Alert::new(1, rule, RequestId::default());
+        let samples = [
+            sample(0, 0.95),
+            sample(500, 0.5),
+            sample(1000, 0.95),
+            sample(1500, 0.95),
+            sample(2000, 0.95),
+        ];
+        let expected = vec![None, None, None, None, Some(AlertState::Firing)];
+        assert_eq!(expected, states(&mut alert, &samples));
+    }
+
+    #[test]
+    fn memory_available_is_derived_from_sample() {
+        let sample = HistorySample {
+            timestamp: 0,
+            cpu: 0.0,
+            cpu_user: 0.0,
+            memory_used: 300,
+            memory_total: 1000,
+        };
+        assert_eq!(700.0, AlertMetric::MemoryAvailable.value(&sample));
+        let rule = AlertRule {
+            metric: AlertMetric::MemoryAvailable,
+            condition: AlertCondition::Below,
+            threshold: 1024.0,
+            duration: Duration::ZERO,
+        };
+        let mut alert = Alert::new(1, rule, RequestId::default());
+        assert_eq!(
+            Some(AlertState::Firing),
+            alert.evaluate(&sample).map(|o| o.state)
+        );
+    }
+}
@@ -1,10 +1,61 @@
-use super::message::{ClientMessage, SubSystemPart, WebsocketSystems};
-use crate::error_chain_fmt;
+use super::message::SubSystemPart;
+use crate::{error_causes, error_chain_fmt};
+use serde::{Deserialize, Serialize};
+
+/// Payload sent to clients when a task fails.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ErrorPayload {
+    /// Stable, machine-readable error code.
+    pub code: String,
+    pub message: String,
+    #[serde(default)]
+    pub details: serde_json::Value,
+    #[serde(default)]
+    pub cause_chain: Vec<String>,
+}
+
+impl From<ErrorPayload> for serde_json::Value {
+    fn from(payload: ErrorPayload) -> Self {
+        serde_json::json!({
+            "code": payload.code,
+            "message": payload.message,
+            "details": payload.details,
+            "cause_chain": payload.cause_chain,
+        })
+    }
+}
+
+/// Errors that can be reported to clients.
+pub trait ClientError: std::error::Error + Sized {
+    fn code(&self) -> &'static str;
+
+    /// Extra information about the error, `null` by default.
+    fn details(&self) -> serde_json::Value {
+        serde_json::Value::Null
+    }
+
+    fn to_payload(&self) -> ErrorPayload {
+        ErrorPayload {
+            code: self.code().into(),
+            message: self.to_string(),
+            details: self.details(),
+            cause_chain: error_causes(self).map(|cause| cause.to_string()).collect(),
+        }
+    }
+}
 
 #[derive(thiserror::Error)]
 pub enum WebsocketError {
     #[error("Failed to parse websocket message.")]
     MessageParseError(#[source] anyhow::Error),
+    #[error("Unknown system: {0:?}, available systems: {}.", .1.join(", "))]
+    UnknownSystem(String, Vec<String>),
+    #[error("Unknown task: {0:?}, available tasks: {}.", .1.join(", "))]
+    UnknownTask(String, Vec<String>),
+    #[error("Not allowed to run task {1:?} on system {0:?}.")]
+    Forbidden(String, String),
+    #[error("Invalid payload for task {0:?}: {1}.")]
+    InvalidPayload(String, #[source] serde_json::Error),
     #[error(transparent)]
     UnexpectedError(#[from] anyhow::Error),
 }
@@ -15,18 +66,68 @@ impl std::fmt::Debug for WebsocketError {
     }
 }
 
-impl From<WebsocketError> for ClientMessage {
-    fn from(e: WebsocketError) -> Self {
-        ClientMessage {
-            system: None,
-            success: false,
-            payload: e.to_string().into(),
+impl SubSystemPart for Result<serde_json::Value, WebsocketError> {
+    fn system(&self) -> Option<String> {
+        None
+    }
+}
+
+impl ClientError for WebsocketError {
+    fn code(&self) -> &'static str {
+        match self {
+            WebsocketError::MessageParseError(_) => "parse_error",
+            WebsocketError::UnknownSystem(..) => "unknown_system",
+            WebsocketError::UnknownTask(..) => "unknown_task",
+            WebsocketError::Forbidden(..) => "forbidden",
+            WebsocketError::InvalidPayload(..) => "invalid_payload",
+            WebsocketError::UnexpectedError(_) => "internal",
+        }
+    }
+
+    fn details(&self) -> serde_json::Value {
+        match self {
+            WebsocketError::UnknownSystem(system, available) => {
+                serde_json::json!({ "system": system, "available": available })
+            }
+            WebsocketError::UnknownTask(task, available) => {
+                serde_json::json!({ "task": task, "available": available })
+            }
+            WebsocketError::Forbidden(system, task) => {
+                serde_json::json!({ "system": system, "task": task })
+            }
+            WebsocketError::InvalidPayload(task, _) => serde_json::json!({ "task": task }),
+            _ => serde_json::Value::Null,
         }
     }
 }
 
-impl SubSystemPart for Result<serde_json::Value, WebsocketError> {
-    fn system(&self) -> Option<WebsocketSystems> {
-        None
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn error_payload_includes_code_and_cause_chain() {
+        let e = serde_json::from_str::<serde_json::Value>("{")
+            .context("Failed to deserialize message.")
+            .map_err(WebsocketError::MessageParseError)
+            .unwrap_err();
+        let payload = e.to_payload();
+        assert_eq!("parse_error", payload.code);
+        assert_eq!("Failed to parse websocket message.", payload.message);
+        assert_eq!(2, payload.cause_chain.len());
+        assert_eq!("Failed to deserialize message.", payload.cause_chain[0]);
+    }
+
+    #[test]
+    fn error_payload_includes_details() {
+        let e = WebsocketError::UnknownTask("some_task".into(), vec!["cpu_load".into()]);
+        let payload = serde_json::Value::from(e.to_payload());
+        assert_eq!("unknown_task", payload["code"]);
+        assert_eq!("some_task", payload["details"]["task"]);
+        assert_eq!(
+            serde_json::json!(["cpu_load"]),
+            payload["details"]["available"]
+        );
     }
 }
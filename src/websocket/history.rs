@@ -0,0 +1,167 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Milliseconds since the unix epoch.
+pub fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|o| o.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Usage measured by the background sampler.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct HistorySample {
+    /// In milliseconds since the unix epoch
+    pub timestamp: u64,
+    /// Fraction of time the cpus were busy, across all cores.
+    pub cpu: f32,
+    /// Fraction of time the cpus spent on user mode, across all cores.
+    pub cpu_user: f32,
+    /// In bytes
+    pub memory_used: u64,
+    /// In bytes
+    pub memory_total: u64,
+}
+
+impl HistorySample {
+    /// Average of `samples`, timestamped with the last one.
+    fn mean(samples: &[Self]) -> Option<Self> {
+        let last = samples.last()?;
+        let n = samples.len();
+        Some(Self {
+            timestamp: last.timestamp,
+            cpu: samples.iter().map(|o| o.cpu).sum::<f32>() / n as f32,
+            cpu_user: samples.iter().map(|o| o.cpu_user).sum::<f32>() / n as f32,
+            memory_used: samples.iter().map(|o| o.memory_used).sum::<u64>() / n as u64,
+            memory_total: last.memory_total,
+        })
+    }
+}
+
+/// Bounded buffer keeping the most recent samples.
+#[derive(Debug)]
+pub struct RingBuffer<T> {
+    items: VecDeque<T>,
+    capacity: usize,
+}
+
+impl<T> RingBuffer<T> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            items: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Adds `item`, dropping the oldest one when full.
+    pub fn push(&mut self, item: T) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.items.len() == self.capacity {
+            self.items.pop_front();
+        }
+        self.items.push_back(item);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.items.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+}
+
+impl RingBuffer<HistorySample> {
+    /// Samples between `from` and `to` (inclusive), averaged into at most `max_points`.
+    pub fn query(
+        &self,
+        from: Option<u64>,
+        to: Option<u64>,
+        max_points: Option<usize>,
+    ) -> Vec<HistorySample> {
+        let samples = self
+            .iter()
+            .filter(|o| from.map(|from| o.timestamp >= from).unwrap_or(true))
+            .filter(|o| to.map(|to| o.timestamp <= to).unwrap_or(true))
+            .cloned()
+            .collect::<Vec<_>>();
+        match max_points {
+            Some(max_points) if max_points > 0 && samples.len() > max_points => {
+                let bucket_size = samples.len().div_ceil(max_points);
+                samples
+                    .chunks(bucket_size)
+                    .filter_map(HistorySample::mean)
+                    .collect()
+            }
+            _ => samples,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(timestamp: u64, cpu: f32) -> HistorySample {
+        HistorySample {
+            timestamp,
+            cpu,
+            cpu_user: cpu,
+            memory_used: timestamp * 10,
+            memory_total: 1000,
+        }
+    }
+
+    fn buffer(n: u64) -> RingBuffer<HistorySample> {
+        let mut buffer = RingBuffer::new(5);
+        for i in 0..n {
+            buffer.push(sample(i, i as f32));
+        }
+        buffer
+    }
+
+    #[test]
+    fn ring_buffer_drops_oldest_samples() {
+        let buffer = buffer(8);
+        assert_eq!(5, buffer.len());
+        let timestamps = buffer.iter().map(|o| o.timestamp).collect::<Vec<_>>();
+        assert_eq!(vec![3, 4, 5, 6, 7], timestamps);
+    }
+
+    #[test]
+    fn query_filters_by_time_range() {
+        let timestamps = buffer(5)
+            .query(Some(1), Some(3), None)
+            .into_iter()
+            .map(|o| o.timestamp)
+            .collect::<Vec<_>>();
+        assert_eq!(vec![1, 2, 3], timestamps);
+    }
+
+    #[test]
+    fn query_downsamples_to_max_points() {
+        let samples = buffer(5).query(None, None, Some(2));
+        let expected = vec![
+            HistorySample {
+                memory_used: 10,
+                ..sample(2, 1.0)
+            },
+            HistorySample {
+                memory_used: 35,
+                ..sample(4, 3.5)
+            },
+        ];
+        assert_eq!(expected, samples);
+    }
+}
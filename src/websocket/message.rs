@@ -1,4 +1,4 @@
-use super::error::WebsocketError;
+use super::error::{ClientError, WebsocketError};
 use actix::{Message, Recipient};
 use anyhow::Context;
 use serde::{Deserialize, Serialize};
@@ -7,26 +7,60 @@ use uuid::Uuid;
 /// Raw message from clients.
 #[derive(Debug, Deserialize)]
 pub struct RawWebsocketMessage {
-    pub system: WebsocketSystems,
+    pub system: String,
     pub task: String,
     #[serde(default = "serde_json::Value::default")]
     pub payload: serde_json::Value,
+    /// Optional identifier chosen by the client, echoed back on every reply.
+    pub request_id: Option<String>,
+}
+
+impl RawWebsocketMessage {
+    /// Best effort extraction of the `request_id`, used when the message
+    /// can't be fully parsed but we still want to tag the error reply.
+    pub fn request_id(message: &str) -> Option<String> {
+        #[derive(Deserialize)]
+        struct RequestId {
+            request_id: Option<String>,
+        }
+
+        serde_json::from_str::<RequestId>(message)
+            .ok()
+            .and_then(|o| o.request_id)
+    }
+}
+
+/// Identifies the task a reply answers.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RequestId {
+    /// Chosen by the client, echoed back on every reply.
+    pub client: Option<String>,
+    /// Unique to each dispatched task, never sent to clients. `None` for
+    /// messages pushed later on, e.g. subscription samples or alert events.
+    pub dispatch: Option<u64>,
+}
+
+impl RequestId {
+    pub fn new(client: Option<String>) -> Self {
+        Self {
+            client,
+            dispatch: None,
+        }
+    }
+
+    /// Same `request_id`, for messages that don't answer the task itself.
+    pub fn pushed(&self) -> Self {
+        Self::new(self.client.clone())
+    }
 }
 
 /// Messages accepted from server.
 #[derive(Debug)]
 pub struct WebsocketMessage {
-    pub system: WebsocketSystems,
+    pub system: String,
     pub task: TaskMessage,
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
-#[serde(rename_all = "snake_case")]
-pub enum WebsocketSystems {
-    PythonRepo,
-    PcUsage,
-}
-
 /// Messages that represent tasks.
 #[derive(Debug, Clone, actix::Message)]
 #[rtype(result = "()")]
@@ -38,6 +72,7 @@ pub struct TaskMessage {
 #[derive(Debug, Clone)]
 pub struct TaskPayload {
     pub id: Uuid,
+    pub request_id: RequestId,
     pub data: serde_json::Value,
 }
 
@@ -53,6 +88,7 @@ impl WebsocketMessage {
                 name: raw.task,
                 payload: TaskPayload {
                     id,
+                    request_id: RequestId::new(raw.request_id),
                     data: raw.payload,
                 },
             },
@@ -63,29 +99,36 @@ impl WebsocketMessage {
 }
 
 /// Messages to send to client.
-#[derive(Debug, Message, Serialize, Deserialize)]
+#[derive(Debug, Clone, Message, Serialize, Deserialize)]
 #[rtype(result = "()")]
 pub struct ClientMessage {
-    pub system: Option<WebsocketSystems>,
+    pub system: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
     pub success: bool,
     pub payload: serde_json::Value,
+    /// Dispatch answered by this message, see `RequestId`.
+    #[serde(skip)]
+    pub dispatch: Option<u64>,
 }
 
 pub trait SubSystemPart {
-    fn system(&self) -> Option<WebsocketSystems>;
+    fn system(&self) -> Option<String>;
 }
 
 pub trait ClientMessager: SubSystemPart {
     fn success(&self) -> bool;
     fn payload(self) -> serde_json::Value;
-    fn to_message(self) -> ClientMessage
+    fn to_message(self, request_id: RequestId) -> ClientMessage
     where
         Self: Sized,
     {
         ClientMessage {
             system: self.system(),
+            request_id: request_id.client,
             success: self.success(),
             payload: self.payload(),
+            dispatch: request_id.dispatch,
         }
     }
 }
@@ -93,7 +136,7 @@ pub trait ClientMessager: SubSystemPart {
 impl<E> ClientMessager for Result<serde_json::Value, E>
 where
     Result<serde_json::Value, E>: SubSystemPart,
-    E: std::error::Error,
+    E: ClientError,
 {
     fn success(&self) -> bool {
         self.is_ok()
@@ -102,7 +145,7 @@ where
     fn payload(self) -> serde_json::Value {
         match self {
             Ok(value) => value,
-            Err(e) => e.to_string().into(),
+            Err(e) => e.to_payload().into(),
         }
     }
 }
@@ -115,6 +158,18 @@ pub struct Connect {
     pub addr: Recipient<ClientMessage>,
 }
 
+/// End connection with a server.
+#[derive(Debug, Message)]
+#[rtype(result = "()")]
+pub struct Disconnect {
+    pub id: Uuid,
+}
+
+/// Number of sessions currently connected to a server.
+#[derive(Debug, Message)]
+#[rtype(result = "usize")]
+pub struct SessionCount;
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -126,6 +181,32 @@ mod tests {
             "task": "some_task",
         });
         let message = serde_json::from_value::<RawWebsocketMessage>(message).unwrap();
-        assert_eq!(WebsocketSystems::PythonRepo, message.system);
+        assert_eq!("python_repo", message.system);
+        assert_eq!(None, message.request_id);
+    }
+
+    #[test]
+    fn correctly_deserialize_request_id() {
+        let message = serde_json::json!({
+            "system": "python_repo",
+            "task": "some_task",
+            "request_id": "abc",
+        });
+        let message = serde_json::from_value::<RawWebsocketMessage>(message).unwrap();
+        assert_eq!(Some("abc".to_string()), message.request_id);
+    }
+
+    #[test]
+    fn request_id_is_extracted_from_invalid_message() {
+        let message = serde_json::json!({
+            "task": 1,
+            "request_id": "abc",
+        })
+        .to_string();
+        assert!(WebsocketMessage::parse(Uuid::new_v4(), &message).is_err());
+        assert_eq!(
+            Some("abc".to_string()),
+            RawWebsocketMessage::request_id(&message)
+        );
     }
 }
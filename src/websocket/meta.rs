@@ -0,0 +1,324 @@
+use super::{
+    error::{ClientError, WebsocketError},
+    message::{ClientMessage, Connect, Disconnect, SessionCount, SubSystemPart, TaskMessage},
+    registry::SubSystemRegistry,
+    subsystem::WebsocketSubSystem,
+};
+use crate::error_chain_fmt;
+use actix::{Actor, Handler, Recipient};
+use anyhow::Context;
+use schemars::{schema_for, JsonSchema};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use uuid::Uuid;
+
+#[derive(thiserror::Error)]
+pub enum MetaError {
+    #[error(transparent)]
+    WebsocketError(#[from] WebsocketError),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for MetaError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ClientError for MetaError {
+    fn code(&self) -> &'static str {
+        match self {
+            MetaError::WebsocketError(e) => e.code(),
+            MetaError::UnexpectedError(_) => "internal",
+        }
+    }
+
+    fn details(&self) -> serde_json::Value {
+        match self {
+            MetaError::WebsocketError(e) => e.details(),
+            _ => serde_json::Value::Null,
+        }
+    }
+}
+
+impl SubSystemPart for Result<serde_json::Value, MetaError> {
+    fn system(&self) -> Option<String> {
+        Some(MetaSystem::NAME.into())
+    }
+}
+
+/// Description of a task, with JSON schemas generated from its Rust types.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct TaskDescription {
+    pub name: String,
+    pub description: String,
+    /// JSON schema of the task payload.
+    pub payload: serde_json::Value,
+    /// JSON schema of the payload sent back on success.
+    pub result: serde_json::Value,
+}
+
+impl TaskDescription {
+    /// Describes a task receiving `P` as payload and replying with `R`.
+    pub fn new<P: JsonSchema, R: JsonSchema>(name: &str, description: &str) -> Self {
+        Self {
+            name: name.into(),
+            description: description.into(),
+            payload: serde_json::to_value(schema_for!(P)).expect("Failed to serialize schema."),
+            result: serde_json::to_value(schema_for!(R)).expect("Failed to serialize schema."),
+        }
+    }
+}
+
+/// Declares the task enum of a subsystem, along with a `descriptions` function
+/// describing each task.
+///
+/// Each variant is documented with the description sent to clients and followed
+/// by `=> Result`, the type sent back on success. Unit variants receive no payload.
+macro_rules! described_tasks {
+    (
+        $(#[$attr:meta])*
+        $vis:vis enum $name:ident {
+            $(
+                $(#[doc = $doc:literal])+
+                $variant:ident $(($payload:ty))? => $result:ty
+            ),* $(,)?
+        }
+    ) => {
+        $(#[$attr])*
+        $vis enum $name {
+            $(
+                $(#[doc = $doc])+
+                $variant $(($payload))?,
+            )*
+        }
+
+        impl $name {
+            /// Descriptions of the tasks, in declaration order.
+            pub fn descriptions() -> Vec<$crate::websocket::meta::TaskDescription> {
+                // Names are reported by serde in declaration order too
+                let mut names = $crate::websocket::subsystem::task_names::<Self>().iter();
+                vec![$(
+                    $crate::websocket::meta::TaskDescription::new::<
+                        described_tasks!(@payload $($payload)?),
+                        $result,
+                    >(
+                        names.next().expect("Missing task name."),
+                        &[$($doc),+].iter().map(|o| o.trim()).collect::<Vec<_>>().join(" "),
+                    ),
+                )*]
+            }
+        }
+    };
+    (@payload) => { () };
+    (@payload $payload:ty) => { $payload };
+}
+
+pub(crate) use described_tasks;
+
+/// Short version of `TaskDescription`, without schemas.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct TaskSummary {
+    pub name: String,
+    pub description: String,
+}
+
+/// Lets clients discover the systems and tasks supported by the server.
+pub struct MetaSystem {
+    sessions: HashMap<Uuid, Recipient<ClientMessage>>,
+    systems: BTreeMap<String, Vec<TaskDescription>>,
+}
+
+impl MetaSystem {
+    /// Describes every system in `registry`, along with the meta system itself.
+    pub fn new(registry: &SubSystemRegistry) -> Self {
+        let mut systems = registry
+            .iter()
+            .map(|(name, handle)| (name.to_string(), handle.tasks.clone()))
+            .collect::<BTreeMap<_, _>>();
+        systems.insert(Self::NAME.into(), Self::tasks());
+        Self {
+            sessions: Default::default(),
+            systems,
+        }
+    }
+
+    fn system_tasks(&self, system: &str) -> Result<&Vec<TaskDescription>, MetaError> {
+        self.systems.get(system).ok_or_else(|| {
+            let available = self.systems.keys().cloned().collect();
+            WebsocketError::UnknownSystem(system.into(), available).into()
+        })
+    }
+
+    fn list_tasks(&self, system: &str) -> Result<serde_json::Value, MetaError> {
+        let tasks = self
+            .system_tasks(system)?
+            .iter()
+            .map(|task| TaskSummary {
+                name: task.name.clone(),
+                description: task.description.clone(),
+            })
+            .collect::<Vec<_>>();
+        serde_json::to_value(tasks)
+            .context("Failed to serialize tasks.")
+            .map_err(MetaError::UnexpectedError)
+    }
+
+    fn describe_task(&self, system: &str, task: &str) -> Result<serde_json::Value, MetaError> {
+        let tasks = self.system_tasks(system)?;
+        let description = tasks.iter().find(|o| o.name == task).ok_or_else(|| {
+            let available = tasks.iter().map(|o| o.name.clone()).collect();
+            WebsocketError::UnknownTask(task.into(), available)
+        })?;
+        serde_json::to_value(description)
+            .context("Failed to serialize task description.")
+            .map_err(MetaError::UnexpectedError)
+    }
+}
+
+impl WebsocketSubSystem for MetaSystem {
+    type Error = MetaError;
+    type Task = Tasks;
+
+    const NAME: &'static str = "meta";
+
+    fn get_address(&self, id: &Uuid) -> Option<&Recipient<ClientMessage>> {
+        self.sessions.get(id)
+    }
+
+    fn tasks() -> Vec<TaskDescription> {
+        Tasks::descriptions()
+    }
+}
+
+impl Actor for MetaSystem {
+    type Context = actix::Context<Self>;
+}
+
+impl Handler<Connect> for MetaSystem {
+    type Result = ();
+
+    #[tracing::instrument(name = "Connecting socket to MetaSystem", skip(self, _ctx))]
+    fn handle(&mut self, message: Connect, _ctx: &mut Self::Context) -> Self::Result {
+        self.sessions.insert(message.id, message.addr);
+    }
+}
+
+impl Handler<Disconnect> for MetaSystem {
+    type Result = ();
+
+    #[tracing::instrument(name = "Disconnecting socket from MetaSystem", skip(self, _ctx))]
+    fn handle(&mut self, message: Disconnect, _ctx: &mut Self::Context) -> Self::Result {
+        self.sessions.remove(&message.id);
+    }
+}
+
+impl Handler<SessionCount> for MetaSystem {
+    type Result = usize;
+
+    fn handle(&mut self, _message: SessionCount, _ctx: &mut Self::Context) -> Self::Result {
+        self.sessions.len()
+    }
+}
+
+/// Dispatcher for task handlers
+impl Handler<TaskMessage> for MetaSystem {
+    type Result = ();
+
+    #[tracing::instrument(name = "Handle task (MetaSystem)", skip(self, _ctx))]
+    fn handle(&mut self, task_message: TaskMessage, _ctx: &mut Self::Context) -> Self::Result {
+        let task = match self.task_from_message(&task_message) {
+            Ok(task) => task,
+            Err(_) => return,
+        };
+
+        let result = match task {
+            Tasks::ListSystems => serde_json::to_value(self.systems.keys().collect::<Vec<_>>())
+                .context("Failed to serialize systems.")
+                .map_err(MetaError::UnexpectedError),
+            Tasks::ListTasks(data) => self.list_tasks(&data.system),
+            Tasks::DescribeTask(data) => self.describe_task(&data.system, &data.task),
+        };
+
+        let payload = task_message.payload;
+        self.send_message(payload.id, payload.request_id, result);
+    }
+}
+
+described_tasks! {
+    #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
+    #[serde(tag = "task", content = "payload", rename_all = "snake_case")]
+    pub enum Tasks {
+        /// Names of the systems supported by the server.
+        ListSystems => Vec<String>,
+        /// Tasks supported by a system.
+        ListTasks(ListTasksPayload) => Vec<TaskSummary>,
+        /// JSON schemas of the payload and result of a task.
+        DescribeTask(DescribeTaskPayload) => TaskDescription,
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
+pub struct ListTasksPayload {
+    /// Name of the system, as returned by `list_systems`.
+    pub system: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
+pub struct DescribeTaskPayload {
+    /// Name of the system, as returned by `list_systems`.
+    pub system: String,
+    /// Name of the task, as returned by `list_tasks`.
+    pub task: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::websocket::{pc_usage, python_repo, subsystem::task_names};
+    use serde::de::DeserializeOwned;
+
+    fn described_names<S: WebsocketSubSystem>() -> Vec<String> {
+        S::tasks().into_iter().map(|task| task.name).collect()
+    }
+
+    fn task_enum_names<T: DeserializeOwned>() -> Vec<String> {
+        task_names::<T>()
+            .iter()
+            .map(|name| name.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn every_task_is_described() {
+        assert_eq!(
+            task_enum_names::<python_repo::Tasks>(),
+            described_names::<python_repo::PythonRepoSystem>()
+        );
+        assert_eq!(
+            task_enum_names::<pc_usage::Tasks>(),
+            described_names::<pc_usage::PcUsageSystem>()
+        );
+        assert_eq!(task_enum_names::<Tasks>(), described_names::<MetaSystem>());
+    }
+
+    #[test]
+    fn descriptions_come_from_variant_docs() {
+        let tasks = pc_usage::Tasks::descriptions();
+        let cpu = tasks.iter().find(|o| o.name == "cpu").unwrap();
+        assert_eq!(
+            "Cpu load per core and across all cores, optionally with the current frequency of each core.",
+            cpu.description
+        );
+        let memory = tasks.iter().find(|o| o.name == "memory").unwrap();
+        assert_eq!(
+            serde_json::to_value(schema_for!(())).unwrap(),
+            memory.payload
+        );
+        assert_eq!(
+            serde_json::to_value(schema_for!(pc_usage::MemoryResult)).unwrap(),
+            memory.result
+        );
+    }
+}
@@ -0,0 +1,328 @@
+//! Host metrics read by `PcUsageSystem`.
+use super::pc_usage::{
+    CpuLoadResult, LoadAverage, MemoryResult, MemoryUsage, MountResult, NetworkResult, SwapUsage,
+};
+use anyhow::Context;
+use std::{collections::VecDeque, io, path::Path, sync::Mutex, time::Duration};
+use systemstat::Platform;
+
+/// Cpu load measurement in progress, call it once the measuring window elapsed
+/// to get the load of each core.
+pub type CpuLoadMeasurement = Box<dyn FnOnce() -> Result<Vec<CpuLoadResult>, anyhow::Error>>;
+
+/// Source of the host metrics reported by `PcUsageSystem`.
+pub trait MetricsSource: Send + Sync {
+    /// Starts measuring the load of each cpu core.
+    fn cpu_load(&self) -> Result<CpuLoadMeasurement, anyhow::Error>;
+
+    fn memory(&self) -> Result<MemoryResult, anyhow::Error>;
+
+    fn mounts(&self) -> Result<Vec<MountResult>, anyhow::Error>;
+
+    /// Fails with `io::ErrorKind::NotFound` when nothing is mounted at `path`.
+    fn mount_at(&self, path: &Path) -> io::Result<MountResult>;
+
+    /// Counters of every network interface, `rates` is never set.
+    fn networks(&self) -> Result<Vec<NetworkResult>, anyhow::Error>;
+
+    fn load_average(&self) -> Result<LoadAverage, anyhow::Error>;
+
+    fn uptime(&self) -> Result<Duration, anyhow::Error>;
+
+    /// In seconds since the unix epoch
+    fn boot_time(&self) -> Result<i64, anyhow::Error>;
+}
+
+/// Metrics of the current host, read with `systemstat`.
+#[derive(Debug, Default)]
+pub struct SystemMetrics;
+
+impl MetricsSource for SystemMetrics {
+    fn cpu_load(&self) -> Result<CpuLoadMeasurement, anyhow::Error> {
+        let sys = systemstat::System::new();
+        let measurement = sys.cpu_load().context("Failed to read cpu load.")?;
+        Ok(Box::new(move || {
+            let cpu = measurement.done().context("Failed to read cpu load.")?;
+            Ok(cpu.iter().map(CpuLoadResult::from).collect())
+        }))
+    }
+
+    fn memory(&self) -> Result<MemoryResult, anyhow::Error> {
+        let sys = systemstat::System::new();
+        let (memory, swap) = sys
+            .memory_and_swap()
+            .context("Failed to read memory usage.")?;
+        let (available, cached) = platform_memory(&memory);
+        Ok(MemoryResult {
+            memory: MemoryUsage {
+                total: memory.total.as_u64(),
+                free: memory.free.as_u64(),
+                available,
+                cached,
+            },
+            swap: SwapUsage {
+                total: swap.total.as_u64(),
+                free: swap.free.as_u64(),
+                used: swap.total.as_u64().saturating_sub(swap.free.as_u64()),
+            },
+        })
+    }
+
+    fn mounts(&self) -> Result<Vec<MountResult>, anyhow::Error> {
+        let sys = systemstat::System::new();
+        let mounts = sys.mounts().context("Failed to read mount points.")?;
+        Ok(mounts.into_iter().map(MountResult::from).collect())
+    }
+
+    fn mount_at(&self, path: &Path) -> io::Result<MountResult> {
+        let sys = systemstat::System::new();
+        sys.mount_at(path).map(MountResult::from)
+    }
+
+    fn networks(&self) -> Result<Vec<NetworkResult>, anyhow::Error> {
+        let sys = systemstat::System::new();
+        let networks = sys
+            .networks()
+            .context("Failed to read network interfaces.")?;
+        let result = networks
+            .into_values()
+            .filter_map(|network| {
+                // Interfaces can go away between both calls
+                let stats = match sys.network_stats(&network.name) {
+                    Ok(stats) => stats,
+                    Err(e) => {
+                        tracing::warn!("Failed to read stats of {:?}: {:?}", network.name, e);
+                        return None;
+                    }
+                };
+                let addresses = network
+                    .addrs
+                    .iter()
+                    .filter_map(|o| match o.addr {
+                        systemstat::IpAddr::V4(addr) => Some(addr.to_string()),
+                        systemstat::IpAddr::V6(addr) => Some(addr.to_string()),
+                        _ => None,
+                    })
+                    .collect();
+                Some(NetworkResult {
+                    name: network.name,
+                    addresses,
+                    rx_bytes: stats.rx_bytes.as_u64(),
+                    tx_bytes: stats.tx_bytes.as_u64(),
+                    rx_packets: stats.rx_packets,
+                    tx_packets: stats.tx_packets,
+                    rx_errors: stats.rx_errors,
+                    tx_errors: stats.tx_errors,
+                    rates: None,
+                })
+            })
+            .collect();
+        Ok(result)
+    }
+
+    fn load_average(&self) -> Result<LoadAverage, anyhow::Error> {
+        let sys = systemstat::System::new();
+        let load_average = sys.load_average().context("Failed to read load average.")?;
+        Ok(LoadAverage {
+            one: load_average.one,
+            five: load_average.five,
+            fifteen: load_average.fifteen,
+        })
+    }
+
+    fn uptime(&self) -> Result<Duration, anyhow::Error> {
+        let sys = systemstat::System::new();
+        sys.uptime().context("Failed to read uptime.")
+    }
+
+    fn boot_time(&self) -> Result<i64, anyhow::Error> {
+        let sys = systemstat::System::new();
+        let boot_time = sys.boot_time().context("Failed to read boot time.")?;
+        Ok(boot_time.timestamp())
+    }
+}
+
+impl From<&systemstat::CPULoad> for CpuLoadResult {
+    fn from(load: &systemstat::CPULoad) -> Self {
+        Self {
+            user: load.user,
+            system: load.system,
+            nice: load.nice,
+            interrupt: load.interrupt,
+            idle: load.idle,
+            iowait: platform_iowait(load),
+            frequency: None,
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn platform_iowait(load: &systemstat::CPULoad) -> Option<f32> {
+    Some(load.platform.iowait)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn platform_iowait(_load: &systemstat::CPULoad) -> Option<f32> {
+    None
+}
+
+/// Available and cached memory, when the platform reports them.
+#[cfg(target_os = "linux")]
+fn platform_memory(memory: &systemstat::Memory) -> (Option<u64>, Option<u64>) {
+    let meminfo = &memory.platform_memory.meminfo;
+    let get = |key: &str| meminfo.get(key).map(|o| o.as_u64());
+    (get("MemAvailable"), get("Cached"))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn platform_memory(_memory: &systemstat::Memory) -> (Option<u64>, Option<u64>) {
+    (None, None)
+}
+
+impl From<systemstat::Filesystem> for MountResult {
+    fn from(fs: systemstat::Filesystem) -> Self {
+        Self {
+            mounted_on: fs.fs_mounted_on,
+            mounted_from: fs.fs_mounted_from,
+            fs_type: fs.fs_type,
+            total: fs.total.as_u64(),
+            free: fs.free.as_u64(),
+            available: fs.avail.as_u64(),
+            inodes_total: fs.files_total,
+            inodes_used: fs.files,
+            inodes_available: fs.files_avail,
+        }
+    }
+}
+
+/// Values returned in order, the last one is repeated once the others were used.
+#[derive(Debug)]
+struct Script<T>(Mutex<VecDeque<T>>);
+
+impl<T: Clone> Script<T> {
+    fn new(values: Vec<T>) -> Self {
+        Self(Mutex::new(values.into()))
+    }
+
+    fn next(&self, name: &str) -> Result<T, anyhow::Error> {
+        let mut values = self.0.lock().unwrap();
+        let value = if values.len() > 1 {
+            values.pop_front()
+        } else {
+            values.front().cloned()
+        };
+        value.with_context(|| format!("No scripted {} values.", name))
+    }
+}
+
+impl<T> Default for Script<T> {
+    fn default() -> Self {
+        Self(Mutex::new(VecDeque::new()))
+    }
+}
+
+/// Metrics replayed from a script, meant for tests.
+///
+/// Each read returns the next scripted value, metrics without values fail.
+#[derive(Debug, Default)]
+pub struct ScriptedMetrics {
+    cpu_loads: Script<Vec<CpuLoadResult>>,
+    memory: Script<MemoryResult>,
+    mounts: Script<Vec<MountResult>>,
+    networks: Script<Vec<NetworkResult>>,
+    load_averages: Script<LoadAverage>,
+    uptimes: Script<Duration>,
+    boot_times: Script<i64>,
+}
+
+impl ScriptedMetrics {
+    /// Load of each core returned by consecutive cpu measurements.
+    pub fn cpu_loads(mut self, values: Vec<Vec<CpuLoadResult>>) -> Self {
+        self.cpu_loads = Script::new(values);
+        self
+    }
+
+    pub fn memory(mut self, values: Vec<MemoryResult>) -> Self {
+        self.memory = Script::new(values);
+        self
+    }
+
+    pub fn mounts(mut self, values: Vec<Vec<MountResult>>) -> Self {
+        self.mounts = Script::new(values);
+        self
+    }
+
+    pub fn networks(mut self, values: Vec<Vec<NetworkResult>>) -> Self {
+        self.networks = Script::new(values);
+        self
+    }
+
+    pub fn load_averages(mut self, values: Vec<LoadAverage>) -> Self {
+        self.load_averages = Script::new(values);
+        self
+    }
+
+    pub fn uptimes(mut self, values: Vec<Duration>) -> Self {
+        self.uptimes = Script::new(values);
+        self
+    }
+
+    pub fn boot_times(mut self, values: Vec<i64>) -> Self {
+        self.boot_times = Script::new(values);
+        self
+    }
+}
+
+impl MetricsSource for ScriptedMetrics {
+    fn cpu_load(&self) -> Result<CpuLoadMeasurement, anyhow::Error> {
+        let cores = self.cpu_loads.next("cpu load")?;
+        Ok(Box::new(move || Ok(cores)))
+    }
+
+    fn memory(&self) -> Result<MemoryResult, anyhow::Error> {
+        self.memory.next("memory")
+    }
+
+    fn mounts(&self) -> Result<Vec<MountResult>, anyhow::Error> {
+        self.mounts.next("mounts")
+    }
+
+    fn mount_at(&self, path: &Path) -> io::Result<MountResult> {
+        let mounts = self.mounts.next("mounts").map_err(io::Error::other)?;
+        mounts
+            .into_iter()
+            .find(|o| Path::new(&o.mounted_on) == path)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Mount point not found."))
+    }
+
+    fn networks(&self) -> Result<Vec<NetworkResult>, anyhow::Error> {
+        self.networks.next("networks")
+    }
+
+    fn load_average(&self) -> Result<LoadAverage, anyhow::Error> {
+        self.load_averages.next("load average")
+    }
+
+    fn uptime(&self) -> Result<Duration, anyhow::Error> {
+        self.uptimes.next("uptime")
+    }
+
+    fn boot_time(&self) -> Result<i64, anyhow::Error> {
+        self.boot_times.next("boot time")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn script_repeats_last_value() {
+        let script = Script::new(vec![1, 2]);
+        let values = (0..4)
+            .map(|_| script.next("test").unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(vec![1, 2, 2, 2], values);
+        assert!(Script::<u8>::default().next("test").is_err());
+    }
+}
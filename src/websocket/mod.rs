@@ -1,6 +1,11 @@
+pub mod alerts;
 pub mod error;
+pub mod history;
 pub mod message;
+pub mod meta;
+pub mod metrics_source;
 pub mod pc_usage;
 pub mod python_repo;
+pub mod registry;
 pub mod route;
 pub mod subsystem;
@@ -0,0 +1,169 @@
+//! Cpu load and frequency.
+use super::{PcUsageError, PcUsageSystem, ReplyTo};
+use actix::{AsyncContext, Handler, Message};
+use anyhow::Context;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::{path::Path, time::Duration};
+
+/// Time window used to measure cpu load.
+const CPU_LOAD_WINDOW: Duration = Duration::from_millis(200);
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, JsonSchema)]
+pub struct CpuPayload {
+    /// Also send the current frequency of each core.
+    #[serde(default)]
+    pub frequency: bool,
+}
+
+#[derive(Debug, Message)]
+#[rtype(result = "()")]
+pub struct GetCpuLoad {
+    pub(super) reply_to: ReplyTo,
+    /// `None` for the per core list sent by `cpu_load`.
+    pub(super) breakdown: Option<CpuPayload>,
+}
+
+/// Cpu usage, as fractions of the time measured.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct CpuLoadResult {
+    pub user: f32,
+    pub system: f32,
+    #[serde(default)]
+    pub nice: f32,
+    #[serde(default)]
+    pub interrupt: f32,
+    #[serde(default)]
+    pub idle: f32,
+    /// Only reported on Linux.
+    #[serde(default)]
+    pub iowait: Option<f32>,
+    /// Current frequency in MHz, only sent by `cpu` when requested.
+    #[serde(default)]
+    pub frequency: Option<u64>,
+}
+
+impl CpuLoadResult {
+    /// Average load of `cores`.
+    pub(super) fn aggregate(cores: &[Self]) -> Self {
+        let n = cores.len().max(1) as f32;
+        let mean = |f: fn(&Self) -> f32| cores.iter().map(f).sum::<f32>() / n;
+        let iowait = cores
+            .iter()
+            .map(|o| o.iowait)
+            .sum::<Option<f32>>()
+            .map(|o| o / n);
+        Self {
+            user: mean(|o| o.user),
+            system: mean(|o| o.system),
+            nice: mean(|o| o.nice),
+            interrupt: mean(|o| o.interrupt),
+            idle: mean(|o| o.idle),
+            iowait,
+            frequency: None,
+        }
+    }
+}
+
+/// Current frequency of a core in MHz, from `devices/system/cpu` in sysfs.
+fn cpu_frequency(sysfs_root: &Path, core: usize) -> Option<u64> {
+    let path = sysfs_root.join(format!(
+        "devices/system/cpu/cpu{}/cpufreq/scaling_cur_freq",
+        core
+    ));
+    let khz = std::fs::read_to_string(path)
+        .ok()?
+        .trim()
+        .parse::<u64>()
+        .ok()?;
+    Some(khz / 1000)
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct CpuResult {
+    pub cores: Vec<CpuLoadResult>,
+    /// Average across all cores.
+    pub aggregate: CpuLoadResult,
+}
+
+impl GetCpuLoad {
+    fn result(
+        &self,
+        cores: &[CpuLoadResult],
+        sysfs_root: &Path,
+    ) -> Result<serde_json::Value, anyhow::Error> {
+        let breakdown = match &self.breakdown {
+            Some(breakdown) => breakdown,
+            None => return serde_json::to_value(cores).context("Failed to serialize cpu result."),
+        };
+        let mut cores = cores.to_vec();
+        if breakdown.frequency {
+            for (i, core) in cores.iter_mut().enumerate() {
+                core.frequency = cpu_frequency(sysfs_root, i);
+            }
+        }
+        let result = CpuResult {
+            aggregate: CpuLoadResult::aggregate(&cores),
+            cores,
+        };
+        serde_json::to_value(result).context("Failed to serialize cpu result.")
+    }
+}
+
+impl Handler<GetCpuLoad> for PcUsageSystem {
+    type Result = ();
+
+    #[tracing::instrument(name = "Handle task GetCpuLoad", skip(self, ctx))]
+    fn handle(&mut self, message: GetCpuLoad, ctx: &mut Self::Context) -> Self::Result {
+        // Join the measurement in flight, if any
+        if let Some(waiters) = self.cpu_load_waiters.as_mut() {
+            waiters.push(message);
+            return;
+        }
+
+        let measurement = match self.metrics.cpu_load() {
+            Ok(measurement) => measurement,
+            Err(e) => {
+                let result = Err(PcUsageError::UnexpectedError(e));
+                self.reply(message.reply_to, result);
+                return;
+            }
+        };
+
+        self.cpu_load_waiters = Some(vec![message]);
+        ctx.run_later(CPU_LOAD_WINDOW, move |act, _ctx| {
+            let cores = measurement();
+
+            for waiter in act.cpu_load_waiters.take().unwrap_or_default() {
+                let result = match &cores {
+                    Ok(cores) => waiter
+                        .result(cores, &act.settings.sysfs_root)
+                        .map_err(PcUsageError::UnexpectedError),
+                    Err(e) => Err(PcUsageError::UnexpectedError(anyhow::anyhow!("{:#}", e))),
+                };
+                act.reply(waiter.reply_to, result);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cpu_aggregate_is_the_mean_of_cores() {
+        let core = |user, iowait| CpuLoadResult {
+            user,
+            idle: 1.0 - user,
+            iowait,
+            ..Default::default()
+        };
+        let aggregate = CpuLoadResult::aggregate(&[core(0.2, Some(0.1)), core(0.6, Some(0.3))]);
+        assert!((aggregate.user - 0.4).abs() < 1e-6);
+        assert!((aggregate.idle - 0.6).abs() < 1e-6);
+        assert!((aggregate.iowait.unwrap() - 0.2).abs() < 1e-6);
+        let aggregate = CpuLoadResult::aggregate(&[core(0.2, Some(0.1)), core(0.6, None)]);
+        assert!(aggregate.iowait.is_none());
+    }
+}
@@ -0,0 +1,186 @@
+//! Filesystem usage and disk IO throughput.
+use super::{PcUsageError, PcUsageSystem, ReplyTo};
+use crate::procfs;
+use actix::{AsyncContext, Handler, Message};
+use anyhow::Context;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::{
+    io,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+/// Time window used to measure disk throughput.
+const DISK_IO_WINDOW: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, JsonSchema)]
+pub struct MountsPayload {
+    /// Mount point to report, every mount point is reported if missing.
+    #[serde(default)]
+    pub path: Option<PathBuf>,
+}
+
+#[derive(Debug, Message)]
+#[rtype(result = "()")]
+pub struct GetMounts {
+    pub(super) reply_to: ReplyTo,
+    pub(super) path: Option<PathBuf>,
+}
+
+/// Filesystem usage of a mount point, sizes are in bytes.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct MountResult {
+    pub mounted_on: String,
+    pub mounted_from: String,
+    pub fs_type: String,
+    pub total: u64,
+    pub free: u64,
+    /// Free bytes available to non-superusers.
+    pub available: u64,
+    pub inodes_total: usize,
+    pub inodes_used: usize,
+    /// Free inodes available to non-superusers.
+    pub inodes_available: usize,
+}
+
+impl Handler<GetMounts> for PcUsageSystem {
+    type Result = ();
+
+    #[tracing::instrument(name = "Handle task GetMounts", skip(self, _ctx))]
+    fn handle(&mut self, message: GetMounts, _ctx: &mut Self::Context) -> Self::Result {
+        let mounts = match &message.path {
+            Some(path) => self.metrics.mount_at(path).map(|o| vec![o]).map_err(|e| {
+                if e.kind() == io::ErrorKind::NotFound {
+                    PcUsageError::InvalidPath(path.display().to_string())
+                } else {
+                    PcUsageError::UnexpectedError(
+                        anyhow::Error::new(e).context("Failed to read mount point."),
+                    )
+                }
+            }),
+            None => self.metrics.mounts().map_err(PcUsageError::UnexpectedError),
+        };
+        let result = mounts.and_then(|mounts| {
+            serde_json::to_value(mounts)
+                .context("Failed to serialize mounts result.")
+                .map_err(PcUsageError::UnexpectedError)
+        });
+        self.reply(message.reply_to, result);
+    }
+}
+
+#[derive(Debug, Message)]
+#[rtype(result = "()")]
+pub struct GetDiskIo {
+    pub(super) reply_to: ReplyTo,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct DiskIoResult {
+    pub name: String,
+    pub read_bytes_per_second: f64,
+    pub write_bytes_per_second: f64,
+    pub reads_per_second: f64,
+    pub writes_per_second: f64,
+    /// Fraction of the window the device was busy, from 0 to 1.
+    pub utilization: f64,
+}
+
+impl DiskIoResult {
+    /// Usage of a device between two measurements taken `elapsed` time apart.
+    fn between(
+        previous: &procfs::DiskStats,
+        current: &procfs::DiskStats,
+        elapsed: Duration,
+    ) -> Self {
+        let seconds = elapsed.as_secs_f64();
+        let rate = |previous: u64, current: u64| current.saturating_sub(previous) as f64 / seconds;
+        let io_time = Duration::from_millis(current.io_time.saturating_sub(previous.io_time));
+        Self {
+            name: current.name.clone(),
+            read_bytes_per_second: rate(previous.read_bytes, current.read_bytes),
+            write_bytes_per_second: rate(previous.written_bytes, current.written_bytes),
+            reads_per_second: rate(previous.reads, current.reads),
+            writes_per_second: rate(previous.writes, current.writes),
+            // The kernel accounts io time in ticks, so it can exceed the window
+            utilization: (io_time.as_secs_f64() / seconds).min(1.0),
+        }
+    }
+}
+
+impl Handler<GetDiskIo> for PcUsageSystem {
+    type Result = ();
+
+    #[tracing::instrument(name = "Handle task GetDiskIo", skip(self, ctx))]
+    fn handle(&mut self, message: GetDiskIo, ctx: &mut Self::Context) -> Self::Result {
+        // Join the measurement in flight, if any
+        if let Some(waiters) = self.disk_io_waiters.as_mut() {
+            waiters.push(message);
+            return;
+        }
+
+        let start = Instant::now();
+        let first = match procfs::disk_stats(&self.settings.procfs_root) {
+            Ok(first) => first,
+            Err(e) => {
+                let result = Err(PcUsageError::UnexpectedError(e));
+                self.reply(message.reply_to, result);
+                return;
+            }
+        };
+
+        self.disk_io_waiters = Some(vec![message]);
+        ctx.run_later(DISK_IO_WINDOW, move |act, _ctx| {
+            let result = procfs::disk_stats(&act.settings.procfs_root).and_then(|second| {
+                let elapsed = start.elapsed();
+                // Devices added meanwhile are reported on the next measurement
+                let result = second
+                    .iter()
+                    .filter_map(|current| {
+                        let previous = first.iter().find(|o| o.name == current.name)?;
+                        Some(DiskIoResult::between(previous, current, elapsed))
+                    })
+                    .collect::<Vec<_>>();
+                serde_json::to_value(result).context("Failed to serialize disk IO result.")
+            });
+
+            for waiter in act.disk_io_waiters.take().unwrap_or_default() {
+                let result = match &result {
+                    Ok(value) => Ok(value.clone()),
+                    Err(e) => Err(PcUsageError::UnexpectedError(anyhow::anyhow!("{:#}", e))),
+                };
+                act.reply(waiter.reply_to, result);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn disk_io_is_computed_from_previous_measurement() {
+        let measurement = |reads, read_bytes, writes, written_bytes, io_time| procfs::DiskStats {
+            name: "sda".into(),
+            reads,
+            read_bytes,
+            writes,
+            written_bytes,
+            io_time,
+        };
+        let previous = measurement(100, 4096, 50, 8192, 1000);
+        let current = measurement(150, 1024 * 1024 + 4096, 60, 8192, 1250);
+        let result = DiskIoResult::between(&previous, &current, Duration::from_millis(500));
+        assert_eq!("sda", result.name);
+        assert_eq!(2.0 * 1024.0 * 1024.0, result.read_bytes_per_second);
+        assert_eq!(0.0, result.write_bytes_per_second);
+        assert_eq!(100.0, result.reads_per_second);
+        assert_eq!(20.0, result.writes_per_second);
+        assert_eq!(0.5, result.utilization);
+        let busy = measurement(150, 0, 60, 0, 1600);
+        let result = DiskIoResult::between(&previous, &busy, Duration::from_millis(500));
+        assert_eq!(1.0, result.utilization);
+    }
+}
@@ -0,0 +1,166 @@
+//! Host information, cgroup usage and pressure stall information.
+use super::{MemoryResult, PcUsageError, PcUsageSystem, ReplyTo};
+use crate::{
+    cgroup,
+    procfs::{self, Pressure},
+    websocket::{
+        history::HistorySample, message::RequestId, metrics_source::MetricsSource,
+        subsystem::WebsocketSubSystem,
+    },
+};
+use actix::{Handler, Message};
+use anyhow::Context;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::{io, path::Path, time::Duration};
+use uuid::Uuid;
+
+#[derive(Debug, Message)]
+#[rtype(result = "()")]
+pub struct GetHostInfo {
+    pub(super) id: Uuid,
+    pub(super) request_id: RequestId,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct HostInfoResult {
+    pub hostname: String,
+    pub kernel_version: String,
+    /// In seconds
+    pub uptime: u64,
+    /// In seconds since the unix epoch
+    pub boot_time: i64,
+    pub load_average: LoadAverage,
+    pub cpu_count: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct LoadAverage {
+    pub one: f32,
+    pub five: f32,
+    pub fifteen: f32,
+}
+
+impl HostInfoResult {
+    fn read(metrics: &dyn MetricsSource, root: &Path) -> Result<Self, anyhow::Error> {
+        let (_, cpu_count) = procfs::cpu_ticks(root)?;
+        Ok(Self {
+            hostname: procfs::hostname(root)?,
+            kernel_version: procfs::kernel_version(root)?,
+            uptime: metrics.uptime()?.as_secs(),
+            boot_time: metrics.boot_time()?,
+            load_average: metrics.load_average()?,
+            cpu_count,
+        })
+    }
+}
+
+impl Handler<GetHostInfo> for PcUsageSystem {
+    type Result = ();
+
+    #[tracing::instrument(name = "Handle task GetHostInfo", skip(self, _ctx))]
+    fn handle(&mut self, message: GetHostInfo, _ctx: &mut Self::Context) -> Self::Result {
+        let result = HostInfoResult::read(self.metrics.as_ref(), &self.settings.procfs_root)
+            .and_then(|o| serde_json::to_value(o).context("Failed to serialize host info."))
+            .map_err(PcUsageError::UnexpectedError);
+        self.send_message(message.id, message.request_id, result);
+    }
+}
+
+#[derive(Debug, Message)]
+#[rtype(result = "()")]
+pub struct GetCgroup {
+    pub(super) reply_to: ReplyTo,
+}
+
+impl Handler<GetCgroup> for PcUsageSystem {
+    type Result = ();
+
+    #[tracing::instrument(name = "Handle task GetCgroup", skip(self, _ctx))]
+    fn handle(&mut self, message: GetCgroup, _ctx: &mut Self::Context) -> Self::Result {
+        let root = &self.settings.cgroup_root;
+        let result = match cgroup::version(root) {
+            Some(_) => cgroup::self_cgroup(&self.settings.procfs_root)
+                .and_then(|o| cgroup::read_usage(root, &o))
+                .and_then(|o| serde_json::to_value(o).context("Failed to serialize cgroup usage."))
+                .map_err(PcUsageError::UnexpectedError),
+            None => Err(PcUsageError::CgroupUnavailable(root.display().to_string())),
+        };
+        self.reply(message.reply_to, result);
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct PressureResult {
+    pub cpu: Pressure,
+    pub memory: Pressure,
+    pub io: Pressure,
+}
+
+impl PressureResult {
+    fn read(root: &Path) -> Result<Self, PcUsageError> {
+        let read = |resource: &str| {
+            procfs::pressure(root, resource).map_err(|e| match e.kind() {
+                io::ErrorKind::NotFound | io::ErrorKind::Unsupported => {
+                    PcUsageError::PsiUnavailable(root.display().to_string())
+                }
+                _ => PcUsageError::UnexpectedError(
+                    anyhow::Error::new(e).context(format!("Failed to read {} pressure.", resource)),
+                ),
+            })
+        };
+        Ok(Self {
+            cpu: read("cpu")?,
+            memory: read("memory")?,
+            io: read("io")?,
+        })
+    }
+}
+
+#[derive(Debug, Message)]
+#[rtype(result = "()")]
+pub struct GetPressure {
+    pub(super) reply_to: ReplyTo,
+}
+
+impl Handler<GetPressure> for PcUsageSystem {
+    type Result = ();
+
+    #[tracing::instrument(name = "Handle task GetPressure", skip(self, _ctx))]
+    fn handle(&mut self, message: GetPressure, _ctx: &mut Self::Context) -> Self::Result {
+        let result = PressureResult::read(&self.settings.procfs_root).and_then(|o| {
+            serde_json::to_value(o)
+                .context("Failed to serialize pressure.")
+                .map_err(PcUsageError::UnexpectedError)
+        });
+        self.reply(message.reply_to, result);
+    }
+}
+
+/// Current host usage, read by the `/metrics` endpoint.
+#[derive(Debug, Message)]
+#[rtype(result = "Result<HostMetrics, anyhow::Error>")]
+pub struct GetHostMetrics;
+
+#[derive(Debug, Clone)]
+pub struct HostMetrics {
+    /// Last background sample, `None` while the sampler is idle.
+    pub sample: Option<HistorySample>,
+    pub memory: MemoryResult,
+    pub load_average: LoadAverage,
+    pub uptime: Duration,
+}
+
+impl Handler<GetHostMetrics> for PcUsageSystem {
+    type Result = Result<HostMetrics, anyhow::Error>;
+
+    #[tracing::instrument(name = "Handle GetHostMetrics", skip(self, _ctx))]
+    fn handle(&mut self, _message: GetHostMetrics, _ctx: &mut Self::Context) -> Self::Result {
+        Ok(HostMetrics {
+            sample: self.latest_sample.clone(),
+            memory: self.metrics.memory()?,
+            load_average: self.metrics.load_average()?,
+            uptime: self.metrics.uptime()?,
+        })
+    }
+}
@@ -0,0 +1,59 @@
+//! Memory and swap usage.
+use super::{PcUsageError, PcUsageSystem, ReplyTo};
+use actix::{Handler, Message};
+use anyhow::Context;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Message)]
+#[rtype(result = "()")]
+pub struct GetMemory {
+    pub(super) reply_to: ReplyTo,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct MemoryResult {
+    pub memory: MemoryUsage,
+    pub swap: SwapUsage,
+}
+
+/// Memory usage, in bytes.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct MemoryUsage {
+    pub total: u64,
+    pub free: u64,
+    /// Memory available for new processes, only reported on Linux.
+    pub available: Option<u64>,
+    /// Memory used by the page cache, only reported on Linux.
+    pub cached: Option<u64>,
+}
+
+/// Swap usage, in bytes.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SwapUsage {
+    pub total: u64,
+    pub free: u64,
+    pub used: u64,
+}
+
+impl MemoryUsage {
+    /// Memory not available for new processes.
+    pub fn used(&self) -> u64 {
+        self.total
+            .saturating_sub(self.available.unwrap_or(self.free))
+    }
+}
+
+impl Handler<GetMemory> for PcUsageSystem {
+    type Result = ();
+
+    #[tracing::instrument(name = "Handle task GetMemory", skip(self, _ctx))]
+    fn handle(&mut self, message: GetMemory, _ctx: &mut Self::Context) -> Self::Result {
+        let result = self
+            .metrics
+            .memory()
+            .and_then(|o| serde_json::to_value(o).context("Failed to serialize memory result."))
+            .map_err(PcUsageError::UnexpectedError);
+        self.reply(message.reply_to, result);
+    }
+}
@@ -0,0 +1,613 @@
+mod cpu;
+mod disk;
+mod host_info;
+mod memory;
+mod network;
+mod processes;
+mod samples;
+mod subscriptions;
+
+pub use cpu::{CpuLoadResult, CpuPayload, CpuResult, GetCpuLoad};
+pub use disk::{DiskIoResult, GetDiskIo, GetMounts, MountResult, MountsPayload};
+pub use host_info::{
+    GetCgroup, GetHostInfo, GetHostMetrics, GetPressure, HostInfoResult, HostMetrics, LoadAverage,
+    PressureResult,
+};
+pub use memory::{GetMemory, MemoryResult, MemoryUsage, SwapUsage};
+pub use network::{GetNetwork, NetworkPayload, NetworkRates, NetworkResult};
+pub use processes::{
+    GetProcessUsage, GetProcesses, ProcessResult, ProcessSortKey, ProcessTarget, ProcessUsage,
+    ProcessUsageResult, ProcessesPayload,
+};
+pub use samples::{
+    AddAlert, GetHistory, HistoryPayload, ListAlerts, RemoveAlert, RemoveAlertPayload,
+    RemoveAlertResult,
+};
+pub use subscriptions::{
+    Subscribe, SubscribePayload, SubscribeResult, Unsubscribe, UnsubscribePayload,
+    UnsubscribeResult,
+};
+
+use super::{
+    alerts::{Alert, AlertInfo, AlertRule},
+    history::{HistorySample, RingBuffer},
+    metrics_source::{CpuLoadMeasurement, MetricsSource},
+};
+use super::{
+    error::{ClientError, WebsocketError},
+    message::{
+        ClientMessage, ClientMessager, Connect, Disconnect, RequestId, SessionCount, SubSystemPart,
+        TaskMessage,
+    },
+    meta::{described_tasks, TaskDescription},
+    subsystem::{parse_task, WebsocketSubSystem},
+};
+use crate::{
+    cgroup::CgroupUsage, configuration::PcUsageSettings, error_chain_fmt, telemetry::spawn_blocking,
+};
+use actix::{Actor, AsyncContext, Handler, Recipient};
+use processes::ProcessWatch;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+use subscriptions::Sampler;
+use uuid::Uuid;
+
+#[derive(thiserror::Error)]
+pub enum PcUsageError {
+    #[error("Invalid path: {0:?}")]
+    InvalidPath(String),
+    #[error("Invalid subscription: {0}")]
+    InvalidSubscription(String),
+    #[error("Unknown process: {0}")]
+    UnknownProcess(ProcessTarget),
+    #[error("Unknown alert: {0}")]
+    UnknownAlert(u64),
+    #[error("No cgroup filesystem mounted at {0:?}")]
+    CgroupUnavailable(String),
+    #[error("Pressure stall information not available in {0:?}")]
+    PsiUnavailable(String),
+    #[error(transparent)]
+    WebsocketError(#[from] WebsocketError),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for PcUsageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ClientError for PcUsageError {
+    fn code(&self) -> &'static str {
+        match self {
+            PcUsageError::InvalidPath(_) => "invalid_path",
+            PcUsageError::InvalidSubscription(_) => "invalid_subscription",
+            PcUsageError::UnknownProcess(_) => "unknown_process",
+            PcUsageError::UnknownAlert(_) => "unknown_alert",
+            PcUsageError::CgroupUnavailable(_) => "cgroup_unavailable",
+            PcUsageError::PsiUnavailable(_) => "psi_unavailable",
+            PcUsageError::WebsocketError(e) => e.code(),
+            PcUsageError::UnexpectedError(_) => "internal",
+        }
+    }
+
+    fn details(&self) -> serde_json::Value {
+        match self {
+            PcUsageError::InvalidPath(path) => serde_json::json!({ "path": path }),
+            PcUsageError::UnknownProcess(target) => serde_json::json!(target),
+            PcUsageError::UnknownAlert(id) => serde_json::json!({ "id": id }),
+            PcUsageError::CgroupUnavailable(root) => serde_json::json!({ "root": root }),
+            PcUsageError::PsiUnavailable(root) => serde_json::json!({ "root": root }),
+            PcUsageError::WebsocketError(e) => e.details(),
+            _ => serde_json::Value::Null,
+        }
+    }
+}
+
+/// Runs a blocking read, such as a scan of `/proc`, outside of the actor.
+async fn read_blocking<F, T, E>(f: F) -> Result<T, E>
+where
+    F: FnOnce() -> Result<T, E> + Send + 'static,
+    T: Send + 'static,
+    E: From<anyhow::Error> + Send + 'static,
+{
+    match spawn_blocking(f).await {
+        Ok(result) => result,
+        Err(e) => Err(anyhow::Error::new(e)
+            .context("Blocking read failed.")
+            .into()),
+    }
+}
+
+impl SubSystemPart for Result<serde_json::Value, PcUsageError> {
+    fn system(&self) -> Option<String> {
+        Some(PcUsageSystem::NAME.into())
+    }
+}
+
+/// Where the result of a measurement is sent.
+#[derive(Debug, Clone)]
+pub enum ReplyTo {
+    /// Session that requested the task.
+    Session { id: Uuid, request_id: RequestId },
+    /// Subscribers of a sampler, see `PcUsageSystem::publish`.
+    Sampler(Uuid),
+}
+
+impl ReplyTo {
+    fn is_session(&self, session: Uuid) -> bool {
+        matches!(self, ReplyTo::Session { id, .. } if *id == session)
+    }
+}
+
+pub struct PcUsageSystem {
+    settings: PcUsageSettings,
+    metrics: Arc<dyn MetricsSource>,
+    sessions: HashMap<Uuid, Recipient<ClientMessage>>,
+    /// Sampler of the active subscriptions, by session and task name.
+    subscriptions: HashMap<(Uuid, String), Uuid>,
+    /// Samplers shared by subscriptions, by id.
+    samplers: HashMap<Uuid, Sampler>,
+    /// Samplers with a measurement in flight, their intervals are skipped
+    /// meanwhile.
+    measuring: HashSet<Uuid>,
+    /// Requests waiting for the cpu load measurement in flight.
+    cpu_load_waiters: Option<Vec<GetCpuLoad>>,
+    /// Requests waiting for the network rate measurement in flight.
+    network_rate_waiters: Option<Vec<GetNetwork>>,
+    /// Requests waiting for the disk IO measurement in flight.
+    disk_io_waiters: Option<Vec<GetDiskIo>>,
+    /// Requests waiting for the process measurement in flight.
+    processes_waiters: Option<Vec<GetProcesses>>,
+    /// Process watched by each `process` sampler.
+    process_watches: HashMap<Uuid, ProcessWatch>,
+    /// Samples taken by the background sampler.
+    history: RingBuffer<HistorySample>,
+    /// Cpu measurement started on the previous sample.
+    sampler_cpu: Option<CpuLoadMeasurement>,
+    /// Alerts registered by each session, evaluated on every sample.
+    alerts: HashMap<Uuid, Vec<Alert>>,
+    next_alert_id: u64,
+    /// Last sample taken, `None` while the sampler is idle.
+    latest_sample: Option<HistorySample>,
+}
+
+impl PcUsageSystem {
+    pub fn new(settings: PcUsageSettings, metrics: Arc<dyn MetricsSource>) -> Self {
+        Self {
+            history: RingBuffer::new(settings.history_size),
+            settings,
+            metrics,
+            sessions: Default::default(),
+            subscriptions: Default::default(),
+            samplers: Default::default(),
+            measuring: Default::default(),
+            cpu_load_waiters: None,
+            network_rate_waiters: None,
+            disk_io_waiters: None,
+            processes_waiters: None,
+            process_watches: Default::default(),
+            sampler_cpu: None,
+            alerts: Default::default(),
+            next_alert_id: 1,
+            latest_sample: None,
+        }
+    }
+
+    fn is_connected(&self, id: &Uuid) -> bool {
+        self.sessions
+            .get(id)
+            .map(|addr| addr.connected())
+            .unwrap_or(false)
+    }
+
+    fn dispatch(
+        &mut self,
+        id: Uuid,
+        request_id: RequestId,
+        task: Tasks,
+        ctx: &mut <Self as Actor>::Context,
+    ) {
+        let addr = ctx.address();
+        match task {
+            Tasks::HostInfo => addr.do_send(GetHostInfo { id, request_id }),
+            Tasks::History(data) => addr.do_send(GetHistory {
+                id,
+                request_id,
+                query: data.unwrap_or_default(),
+            }),
+            Tasks::AddAlert(rule) => addr.do_send(AddAlert {
+                id,
+                request_id,
+                rule,
+            }),
+            Tasks::ListAlerts => addr.do_send(ListAlerts { id, request_id }),
+            Tasks::RemoveAlert(data) => addr.do_send(RemoveAlert {
+                id,
+                request_id,
+                alert_id: data.id,
+            }),
+            Tasks::Subscribe(data) => addr.do_send(Subscribe {
+                id,
+                request_id,
+                task: *data.task,
+                interval: data.interval,
+            }),
+            Tasks::Unsubscribe(data) => addr.do_send(Unsubscribe {
+                id,
+                request_id,
+                task: data.task,
+            }),
+            task => self.measure(ReplyTo::Session { id, request_id }, task, ctx),
+        }
+    }
+
+    /// Runs a streamable task, sending the result to `reply_to`.
+    fn measure(&mut self, reply_to: ReplyTo, task: Tasks, ctx: &mut <Self as Actor>::Context) {
+        let addr = ctx.address();
+        match task {
+            Tasks::CpuLoad => addr.do_send(GetCpuLoad {
+                reply_to,
+                breakdown: None,
+            }),
+            Tasks::Cpu(data) => addr.do_send(GetCpuLoad {
+                reply_to,
+                breakdown: Some(data.unwrap_or_default()),
+            }),
+            Tasks::Memory => addr.do_send(GetMemory { reply_to }),
+            Tasks::Mounts(data) => addr.do_send(GetMounts {
+                reply_to,
+                path: data.and_then(|o| o.path),
+            }),
+            Tasks::Network(data) => addr.do_send(GetNetwork {
+                reply_to,
+                rate: data.map(|o| o.rate).unwrap_or_default(),
+            }),
+            Tasks::DiskIo => addr.do_send(GetDiskIo { reply_to }),
+            Tasks::Processes(data) => {
+                let data = data.unwrap_or_default();
+                addr.do_send(GetProcesses {
+                    reply_to,
+                    sort_by: data.sort_by,
+                    limit: data.limit,
+                })
+            }
+            Tasks::Process(target) => addr.do_send(GetProcessUsage { reply_to, target }),
+            Tasks::Cgroup => addr.do_send(GetCgroup { reply_to }),
+            Tasks::Pressure => addr.do_send(GetPressure { reply_to }),
+            task => {
+                let e = anyhow::anyhow!("Task {:?} is not streamable.", task.name());
+                self.reply(reply_to, Err(PcUsageError::UnexpectedError(e)));
+            }
+        }
+    }
+
+    /// Sends the result of a measurement to `reply_to`.
+    fn reply(&mut self, reply_to: ReplyTo, result: Result<serde_json::Value, PcUsageError>) {
+        match reply_to {
+            ReplyTo::Session { id, request_id } => self.send_message(id, request_id, result),
+            ReplyTo::Sampler(sampler_id) => {
+                self.publish(sampler_id, result.to_message(RequestId::default()))
+            }
+        }
+    }
+}
+
+impl WebsocketSubSystem for PcUsageSystem {
+    type Error = PcUsageError;
+    type Task = Tasks;
+
+    const NAME: &'static str = "pc_usage";
+
+    fn get_address(&self, id: &Uuid) -> Option<&Recipient<ClientMessage>> {
+        self.sessions.get(id)
+    }
+
+    fn inner_tasks(task: &TaskMessage) -> Vec<String> {
+        match parse_task::<Tasks>(task) {
+            Ok(Tasks::Subscribe(data)) => vec![data.task.name()],
+            _ => Vec::new(),
+        }
+    }
+
+    fn tasks() -> Vec<TaskDescription> {
+        Tasks::descriptions()
+    }
+}
+
+impl Actor for PcUsageSystem {
+    type Context = actix::Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        self.sample();
+        ctx.run_interval(self.settings.sample_interval, |act, _ctx| act.sample());
+    }
+}
+
+impl Handler<Connect> for PcUsageSystem {
+    type Result = ();
+
+    #[tracing::instrument(name = "Connecting socket to PcUsageSystem", skip(self, _ctx))]
+    fn handle(&mut self, message: Connect, _ctx: &mut Self::Context) -> Self::Result {
+        self.sessions.insert(message.id, message.addr);
+    }
+}
+
+impl Handler<Disconnect> for PcUsageSystem {
+    type Result = ();
+
+    #[tracing::instrument(name = "Disconnecting socket from PcUsageSystem", skip(self, ctx))]
+    fn handle(&mut self, message: Disconnect, ctx: &mut Self::Context) -> Self::Result {
+        self.sessions.remove(&message.id);
+        let subscriptions = self
+            .subscriptions
+            .keys()
+            .filter(|(id, _)| *id == message.id)
+            .map(|(_, name)| name.clone())
+            .collect::<Vec<_>>();
+        for name in subscriptions {
+            self.unsubscribe(message.id, &name, ctx);
+        }
+        if let Some(waiters) = self.cpu_load_waiters.as_mut() {
+            waiters.retain(|waiter| !waiter.reply_to.is_session(message.id));
+        }
+        if let Some(waiters) = self.network_rate_waiters.as_mut() {
+            waiters.retain(|waiter| !waiter.reply_to.is_session(message.id));
+        }
+        if let Some(waiters) = self.disk_io_waiters.as_mut() {
+            waiters.retain(|waiter| !waiter.reply_to.is_session(message.id));
+        }
+        if let Some(waiters) = self.processes_waiters.as_mut() {
+            waiters.retain(|waiter| !waiter.reply_to.is_session(message.id));
+        }
+        self.alerts.remove(&message.id);
+    }
+}
+
+impl Handler<SessionCount> for PcUsageSystem {
+    type Result = usize;
+
+    fn handle(&mut self, _message: SessionCount, _ctx: &mut Self::Context) -> Self::Result {
+        self.sessions.len()
+    }
+}
+
+/// Dispatcher for task handlers
+impl Handler<TaskMessage> for PcUsageSystem {
+    type Result = ();
+
+    #[tracing::instrument(name = "Handle task (PcUsageSystem)", skip(self, ctx))]
+    fn handle(&mut self, task_message: TaskMessage, ctx: &mut Self::Context) -> Self::Result {
+        let task = match self.task_from_message(&task_message) {
+            Ok(task) => task,
+            Err(_) => return,
+        };
+
+        let payload = task_message.payload;
+        self.dispatch(payload.id, payload.request_id, task, ctx);
+    }
+}
+
+described_tasks! {
+    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, JsonSchema)]
+    #[serde(tag = "task", content = "payload", rename_all = "snake_case")]
+    pub enum Tasks {
+        /// Cpu load per core.
+        CpuLoad => Vec<CpuLoadResult>,
+        /// Cpu load per core and across all cores, optionally with the current
+        /// frequency of each core.
+        Cpu(Option<CpuPayload>) => CpuResult,
+        /// Memory and swap usage, in bytes.
+        Memory => MemoryResult,
+        /// Filesystem usage per mount point, optionally only for the one mounted
+        /// at `path`.
+        Mounts(Option<MountsPayload>) => Vec<MountResult>,
+        /// Addresses and traffic counters per network interface, with `rate` also
+        /// reports bytes per second.
+        Network(Option<NetworkPayload>) => Vec<NetworkResult>,
+        /// Throughput, IOPS and utilisation per block device, measured over a
+        /// short sampling window.
+        DiskIo => Vec<DiskIoResult>,
+        /// Top processes sorted by cpu or resident memory, cpu usage is measured
+        /// over a short sampling window.
+        Processes(Option<ProcessesPayload>) => Vec<ProcessResult>,
+        /// Usage of a process given by `pid` or `name` (glob pattern), when
+        /// subscribed a final message is sent once it exits.
+        Process(ProcessTarget) => ProcessUsageResult,
+        /// Hostname, kernel version, uptime, load averages and cpu count.
+        HostInfo => HostInfoResult,
+        /// Cpu, memory and pids usage and limits of the cgroup (v2 or v1) the
+        /// server runs in.
+        Cgroup => CgroupUsage,
+        /// Pressure stall information (PSI) of cpu, memory and io, requires
+        /// Linux 4.20 or later.
+        Pressure => PressureResult,
+        /// Cpu and memory samples taken in the background, optionally within a
+        /// time range and downsampled.
+        History(Option<HistoryPayload>) => Vec<HistorySample>,
+        /// Registers an alert rule, an `AlertEvent` is sent with the same
+        /// `request_id` whenever it fires or resolves.
+        AddAlert(AlertRule) => AlertInfo,
+        /// Alerts registered by the client.
+        ListAlerts => Vec<AlertInfo>,
+        /// Removes an alert registered by the client.
+        RemoveAlert(RemoveAlertPayload) => RemoveAlertResult,
+        /// Periodically runs a task, sending every result with the subscribe
+        /// `request_id`.
+        Subscribe(SubscribePayload) => SubscribeResult,
+        /// Stops a subscription.
+        Unsubscribe(UnsubscribePayload) => UnsubscribeResult,
+    }
+}
+
+impl Tasks {
+    /// Tasks that can be periodically sent to a client using `subscribe`.
+    fn is_streamable(&self) -> bool {
+        matches!(
+            self,
+            Tasks::CpuLoad
+                | Tasks::Cpu(_)
+                | Tasks::Memory
+                | Tasks::Mounts(_)
+                | Tasks::Network(_)
+                | Tasks::DiskIo
+                | Tasks::Processes(_)
+                | Tasks::Process(_)
+                | Tasks::Cgroup
+                | Tasks::Pressure
+        )
+    }
+
+    fn name(&self) -> String {
+        serde_json::to_value(self)
+            .ok()
+            .and_then(|o| o["task"].as_str().map(String::from))
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::websocket::{
+        alerts::{AlertCondition, AlertMetric},
+        message::WebsocketMessage,
+    };
+    use std::time::Duration;
+
+    fn parse(message: serde_json::Value) -> Result<Tasks, WebsocketError> {
+        let message = WebsocketMessage::parse(Uuid::new_v4(), &message.to_string()).unwrap();
+        parse_task::<Tasks>(&message.task)
+    }
+
+    #[test]
+    fn subscribe_reports_its_inner_task() {
+        let inner_tasks = |message: serde_json::Value| {
+            let message = WebsocketMessage::parse(Uuid::new_v4(), &message.to_string()).unwrap();
+            PcUsageSystem::inner_tasks(&message.task)
+        };
+        let message = serde_json::json!({
+            "system": "pc_usage",
+            "task": "subscribe",
+            "payload": { "task": "processes", "interval": 1000 }
+        });
+        assert_eq!(vec!["processes".to_string()], inner_tasks(message));
+        let message = serde_json::json!({
+            "system": "pc_usage",
+            "task": "cpu_load",
+        });
+        assert!(inner_tasks(message).is_empty());
+    }
+
+    #[test]
+    fn correctly_deserialize_task() {
+        let message = serde_json::json!({
+            "system": "pc_usage",
+            "task": "cpu_load",
+        });
+        assert_eq!(Tasks::CpuLoad, parse(message).unwrap());
+        let message = serde_json::json!({
+            "system": "pc_usage",
+            "task": "memory",
+        });
+        assert_eq!(Tasks::Memory, parse(message).unwrap());
+    }
+
+    #[test]
+    fn correctly_deserialize_mounts_task() {
+        let message = serde_json::json!({
+            "system": "pc_usage",
+            "task": "mounts",
+        });
+        assert_eq!(Tasks::Mounts(None), parse(message).unwrap());
+        let message = serde_json::json!({
+            "system": "pc_usage",
+            "task": "mounts",
+            "payload": { "path": "/" },
+        });
+        let expected = Tasks::Mounts(Some(MountsPayload {
+            path: Some("/".into()),
+        }));
+        assert_eq!(expected, parse(message).unwrap());
+    }
+
+    #[test]
+    fn correctly_deserialize_add_alert_task() {
+        let message = serde_json::json!({
+            "system": "pc_usage",
+            "task": "add_alert",
+            "payload": { "metric": "cpu_user", "condition": "above", "threshold": 0.9 },
+        });
+        let expected = Tasks::AddAlert(AlertRule {
+            metric: AlertMetric::CpuUser,
+            condition: AlertCondition::Above,
+            threshold: 0.9,
+            duration: Duration::ZERO,
+        });
+        assert_eq!(expected, parse(message).unwrap());
+    }
+
+    #[test]
+    fn correctly_deserialize_processes_task() {
+        let message = serde_json::json!({
+            "system": "pc_usage",
+            "task": "processes",
+            "payload": { "sort_by": "memory" },
+        });
+        let expected = Tasks::Processes(Some(ProcessesPayload {
+            sort_by: ProcessSortKey::Memory,
+            limit: 10,
+        }));
+        assert_eq!(expected, parse(message).unwrap());
+    }
+
+    #[test]
+    fn correctly_deserialize_process_task() {
+        let message = serde_json::json!({
+            "system": "pc_usage",
+            "task": "process",
+            "payload": { "pid": 1 },
+        });
+        let expected = Tasks::Process(ProcessTarget::Pid { pid: 1 });
+        assert_eq!(expected, parse(message).unwrap());
+        let message = serde_json::json!({
+            "system": "pc_usage",
+            "task": "process",
+            "payload": { "name": "python*" },
+        });
+        let expected = Tasks::Process(ProcessTarget::Name {
+            name: "python*".into(),
+        });
+        assert_eq!(expected, parse(message).unwrap());
+    }
+
+    #[test]
+    fn correctly_deserialize_subscribe_task() {
+        let message = serde_json::json!({
+            "system": "pc_usage",
+            "task": "subscribe",
+            "payload": { "task": "cpu_load", "interval": 1000 }
+        });
+        let expected = Tasks::Subscribe(SubscribePayload {
+            task: Box::new(Tasks::CpuLoad),
+            interval: Duration::from_millis(1000),
+        });
+        assert_eq!(expected, parse(message).unwrap());
+    }
+
+    #[test]
+    fn invalid_payload_error_names_missing_field() {
+        let message = serde_json::json!({
+            "system": "pc_usage",
+            "task": "subscribe",
+            "payload": { "task": "cpu_load" }
+        });
+        let error = parse(message).unwrap_err();
+        assert!(error.to_string().contains("`interval`"), "{}", error);
+    }
+}
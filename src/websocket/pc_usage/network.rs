@@ -0,0 +1,146 @@
+//! Network interfaces and their traffic rates.
+use super::{PcUsageError, PcUsageSystem, ReplyTo};
+use actix::{AsyncContext, Handler, Message};
+use anyhow::Context;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+/// Time window used to measure network rates.
+const NETWORK_RATE_WINDOW: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, JsonSchema)]
+pub struct NetworkPayload {
+    /// Also measure bytes per second, the result is sent after a short sampling window.
+    #[serde(default)]
+    pub rate: bool,
+}
+
+#[derive(Debug, Message)]
+#[rtype(result = "()")]
+pub struct GetNetwork {
+    pub(super) reply_to: ReplyTo,
+    pub(super) rate: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct NetworkResult {
+    pub name: String,
+    pub addresses: Vec<String>,
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+    pub rx_packets: u64,
+    pub tx_packets: u64,
+    pub rx_errors: u64,
+    pub tx_errors: u64,
+    /// Only sent when requested with `rate`.
+    pub rates: Option<NetworkRates>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct NetworkRates {
+    pub rx_bytes_per_second: f64,
+    pub tx_bytes_per_second: f64,
+}
+
+impl NetworkResult {
+    /// Sets `rates` using a previous measurement, taken `elapsed` time ago.
+    fn with_rates(mut self, previous: &[Self], elapsed: Duration) -> Self {
+        let seconds = elapsed.as_secs_f64();
+        self.rates = previous
+            .iter()
+            .find(|o| o.name == self.name)
+            .filter(|_| seconds > 0.0)
+            .map(|previous| NetworkRates {
+                rx_bytes_per_second: self.rx_bytes.saturating_sub(previous.rx_bytes) as f64
+                    / seconds,
+                tx_bytes_per_second: self.tx_bytes.saturating_sub(previous.tx_bytes) as f64
+                    / seconds,
+            });
+        self
+    }
+}
+
+impl Handler<GetNetwork> for PcUsageSystem {
+    type Result = ();
+
+    #[tracing::instrument(name = "Handle task GetNetwork", skip(self, ctx))]
+    fn handle(&mut self, message: GetNetwork, ctx: &mut Self::Context) -> Self::Result {
+        if !message.rate {
+            let result = self
+                .metrics
+                .networks()
+                .and_then(|o| {
+                    serde_json::to_value(o).context("Failed to serialize network result.")
+                })
+                .map_err(PcUsageError::UnexpectedError);
+            self.reply(message.reply_to, result);
+            return;
+        }
+
+        // Join the measurement in flight, if any
+        if let Some(waiters) = self.network_rate_waiters.as_mut() {
+            waiters.push(message);
+            return;
+        }
+
+        let start = Instant::now();
+        let first = match self.metrics.networks() {
+            Ok(first) => first,
+            Err(e) => {
+                let result = Err(PcUsageError::UnexpectedError(e));
+                self.reply(message.reply_to, result);
+                return;
+            }
+        };
+
+        self.network_rate_waiters = Some(vec![message]);
+        ctx.run_later(NETWORK_RATE_WINDOW, move |act, _ctx| {
+            let result = act.metrics.networks().and_then(|second| {
+                let elapsed = start.elapsed();
+                let result = second
+                    .into_iter()
+                    .map(|o| o.with_rates(&first, elapsed))
+                    .collect::<Vec<_>>();
+                serde_json::to_value(result).context("Failed to serialize network result.")
+            });
+
+            for waiter in act.network_rate_waiters.take().unwrap_or_default() {
+                let result = match &result {
+                    Ok(value) => Ok(value.clone()),
+                    Err(e) => Err(PcUsageError::UnexpectedError(anyhow::anyhow!("{:#}", e))),
+                };
+                act.reply(waiter.reply_to, result);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn network_rates_are_computed_from_previous_measurement() {
+        let measurement = |rx_bytes, tx_bytes| NetworkResult {
+            name: "eth0".into(),
+            addresses: vec![],
+            rx_bytes,
+            tx_bytes,
+            rx_packets: 0,
+            tx_packets: 0,
+            rx_errors: 0,
+            tx_errors: 0,
+            rates: None,
+        };
+        let previous = vec![measurement(1000, 500)];
+        let rates = measurement(2000, 600)
+            .with_rates(&previous, Duration::from_millis(500))
+            .rates
+            .unwrap();
+        assert_eq!(2000.0, rates.rx_bytes_per_second);
+        assert_eq!(200.0, rates.tx_bytes_per_second);
+        let current = measurement(2000, 600).with_rates(&[], Duration::from_millis(500));
+        assert!(current.rates.is_none());
+    }
+}
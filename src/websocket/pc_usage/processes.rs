@@ -0,0 +1,480 @@
+//! Top processes and the usage of a single process.
+use super::{read_blocking, PcUsageError, PcUsageSystem, ReplyTo};
+use crate::procfs;
+use actix::{
+    Actor, ActorFutureExt, AsyncContext, ContextFutureSpawner, Handler, Message, WrapFuture,
+};
+use anyhow::Context;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, path::Path, time::Duration};
+
+/// Time window used to measure process cpu usage.
+const PROCESS_CPU_WINDOW: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ProcessSortKey {
+    #[default]
+    Cpu,
+    Memory,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, JsonSchema)]
+pub struct ProcessesPayload {
+    /// `cpu` by default.
+    #[serde(default)]
+    pub sort_by: ProcessSortKey,
+    /// Number of processes to send, 10 by default.
+    #[serde(default = "ProcessesPayload::default_limit")]
+    pub limit: usize,
+}
+
+impl ProcessesPayload {
+    fn default_limit() -> usize {
+        10
+    }
+}
+
+impl Default for ProcessesPayload {
+    fn default() -> Self {
+        Self {
+            sort_by: Default::default(),
+            limit: Self::default_limit(),
+        }
+    }
+}
+
+/// Process to monitor.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
+#[serde(untagged)]
+pub enum ProcessTarget {
+    Pid {
+        pid: u32,
+    },
+    /// Glob pattern matched against process names, the lowest matching pid is used.
+    Name {
+        name: String,
+    },
+}
+
+impl std::fmt::Display for ProcessTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProcessTarget::Pid { pid } => write!(f, "pid {}", pid),
+            ProcessTarget::Name { name } => write!(f, "no process name matches {:?}", name),
+        }
+    }
+}
+
+impl ProcessTarget {
+    /// Finds the pid of a running process matching the target.
+    pub(super) fn resolve(&self, root: &Path) -> Result<u32, PcUsageError> {
+        let pid = match self {
+            ProcessTarget::Pid { pid } => {
+                Some(*pid).filter(|pid| procfs::process_exists(root, *pid))
+            }
+            ProcessTarget::Name { name } => {
+                // Invalid patterns are matched literally
+                let pattern = glob::Pattern::new(name)
+                    .unwrap_or_else(|_| glob::Pattern::new(&glob::Pattern::escape(name)).unwrap());
+                let mut processes = procfs::read_processes(root)?;
+                processes.sort_by_key(|o| o.pid);
+                processes
+                    .into_iter()
+                    .find(|o| pattern.matches(&o.name))
+                    .map(|o| o.pid)
+            }
+        };
+        pid.ok_or_else(|| PcUsageError::UnknownProcess(self.clone()))
+    }
+}
+
+#[derive(Debug, Message)]
+#[rtype(result = "()")]
+pub struct GetProcesses {
+    pub(super) reply_to: ReplyTo,
+    pub(super) sort_by: ProcessSortKey,
+    pub(super) limit: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ProcessResult {
+    pub pid: u32,
+    pub name: String,
+    pub cmdline: String,
+    pub user: Option<String>,
+    /// Percentage of a single cpu, can be over 100 for multithreaded processes.
+    /// `null` for processes started during the measuring window.
+    pub cpu: Option<f32>,
+    /// Resident memory, in bytes.
+    pub rss: u64,
+    pub state: String,
+}
+
+/// Processes and total cpu ticks read at the same time.
+struct ProcessSample {
+    cpu_ticks: u64,
+    cpus: usize,
+    processes: Vec<procfs::Process>,
+}
+
+impl ProcessSample {
+    fn read(root: &Path) -> Result<Self, anyhow::Error> {
+        let (cpu_ticks, cpus) = procfs::cpu_ticks(root)?;
+        let processes = procfs::read_processes(root)?;
+        Ok(Self {
+            cpu_ticks,
+            cpus,
+            processes,
+        })
+    }
+
+    /// Usage of every process since the `previous` sample.
+    fn usage_since(self, previous: &Self) -> Vec<ProcessResult> {
+        let users = procfs::users();
+        let previous_ticks = previous
+            .processes
+            .iter()
+            .map(|o| (o.pid, o.cpu_ticks))
+            .collect::<HashMap<_, _>>();
+        let elapsed_ticks = self.cpu_ticks.saturating_sub(previous.cpu_ticks);
+        let cpus = self.cpus;
+        self.processes
+            .into_iter()
+            .map(|process| {
+                // Ticks spent before the first sample are unknown for new processes
+                let cpu = previous_ticks.get(&process.pid).map(|previous| {
+                    let ticks = process.cpu_ticks.saturating_sub(*previous);
+                    cpu_percent(ticks, elapsed_ticks, cpus)
+                });
+                ProcessResult {
+                    pid: process.pid,
+                    user: process.uid.and_then(|uid| users.get(&uid).cloned()),
+                    name: process.name,
+                    cmdline: process.cmdline,
+                    cpu,
+                    rss: process.rss,
+                    state: process.state.to_string(),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Percentage of a single cpu used by a process, given the ticks spent by the process
+/// and by all `cpus` over the same period.
+fn cpu_percent(process_ticks: u64, elapsed_ticks: u64, cpus: usize) -> f32 {
+    if elapsed_ticks == 0 {
+        return 0.0;
+    }
+    (process_ticks as f32 / elapsed_ticks as f32) * cpus as f32 * 100.0
+}
+
+/// Sorts `processes` by `sort_by` (descending) and keeps the first `limit`.
+fn top_processes(
+    mut processes: Vec<ProcessResult>,
+    sort_by: ProcessSortKey,
+    limit: usize,
+) -> Vec<ProcessResult> {
+    match sort_by {
+        // Processes without cpu usage go last
+        ProcessSortKey::Cpu => processes.sort_by(|a, b| {
+            let cpu = |o: &ProcessResult| o.cpu.unwrap_or(f32::NEG_INFINITY);
+            cpu(b).total_cmp(&cpu(a))
+        }),
+        ProcessSortKey::Memory => processes.sort_by_key(|o| std::cmp::Reverse(o.rss)),
+    }
+    processes.truncate(limit);
+    processes
+}
+
+impl Handler<GetProcesses> for PcUsageSystem {
+    type Result = ();
+
+    #[tracing::instrument(name = "Handle task GetProcesses", skip(self, ctx))]
+    fn handle(&mut self, message: GetProcesses, ctx: &mut Self::Context) -> Self::Result {
+        // Join the measurement in flight, if any
+        if let Some(waiters) = self.processes_waiters.as_mut() {
+            waiters.push(message);
+            return;
+        }
+
+        self.processes_waiters = Some(vec![message]);
+        let root = self.settings.procfs_root.clone();
+        read_blocking(move || ProcessSample::read(&root))
+            .into_actor(self)
+            .map(|first, act, ctx| {
+                let first = match first {
+                    Ok(first) => first,
+                    Err(e) => return act.reply_processes(Err(e)),
+                };
+                ctx.run_later(PROCESS_CPU_WINDOW, move |act, ctx| {
+                    let root = act.settings.procfs_root.clone();
+                    read_blocking(move || {
+                        ProcessSample::read(&root).map(|second| second.usage_since(&first))
+                    })
+                    .into_actor(act)
+                    .map(|processes, act, _ctx| act.reply_processes(processes))
+                    .spawn(ctx);
+                });
+            })
+            .spawn(ctx);
+    }
+}
+
+impl PcUsageSystem {
+    /// Sends the top `processes` requested by every waiter.
+    fn reply_processes(&mut self, processes: Result<Vec<ProcessResult>, anyhow::Error>) {
+        for waiter in self.processes_waiters.take().unwrap_or_default() {
+            let result = match &processes {
+                Ok(processes) => {
+                    let processes = top_processes(processes.clone(), waiter.sort_by, waiter.limit);
+                    serde_json::to_value(processes)
+                        .context("Failed to serialize processes result.")
+                        .map_err(PcUsageError::UnexpectedError)
+                }
+                Err(e) => Err(PcUsageError::UnexpectedError(anyhow::anyhow!("{:#}", e))),
+            };
+            self.reply(waiter.reply_to, result);
+        }
+    }
+}
+
+#[derive(Debug, Message)]
+#[rtype(result = "()")]
+pub struct GetProcessUsage {
+    pub(super) reply_to: ReplyTo,
+    pub(super) target: ProcessTarget,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ProcessUsageResult {
+    pub pid: u32,
+    /// Only `null` when the process exited before it could be read.
+    pub name: Option<String>,
+    /// Set on the last message sent for a process, `usage` is `null` in that case.
+    pub exited: bool,
+    pub usage: Option<ProcessUsage>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ProcessUsage {
+    /// Percentage of a single cpu, can be over 100 for multithreaded processes.
+    pub cpu: f32,
+    /// Resident memory, in bytes.
+    pub rss: u64,
+    pub threads: u64,
+    /// `null` when not allowed to read the file descriptors of the process.
+    pub open_fds: Option<usize>,
+    /// `null` when not allowed to read the io counters of the process.
+    pub read_bytes: Option<u64>,
+    pub write_bytes: Option<u64>,
+}
+
+/// Process sampled by a `process` subscription.
+#[derive(Debug)]
+pub(super) struct ProcessWatch {
+    pub(super) pid: u32,
+    /// Whether the final message was already sent.
+    pub(super) exited: bool,
+}
+
+impl PcUsageSystem {
+    /// Watch of the process `pid`, if it is sampled for `reply_to`.
+    fn process_watch(&mut self, reply_to: &ReplyTo, pid: u32) -> Option<&mut ProcessWatch> {
+        match reply_to {
+            ReplyTo::Sampler(sampler_id) => self
+                .process_watches
+                .get_mut(sampler_id)
+                .filter(|o| o.pid == pid),
+            ReplyTo::Session { .. } => None,
+        }
+    }
+
+    /// Sends the final message of a process, stopping its subscription (if any).
+    fn process_exited(
+        &mut self,
+        reply_to: ReplyTo,
+        pid: u32,
+        name: Option<String>,
+        ctx: &mut <Self as Actor>::Context,
+    ) {
+        let mut stop_sampler = None;
+        if let Some(watch) = self.process_watch(&reply_to, pid) {
+            // The final message was already sent
+            if watch.exited {
+                return;
+            }
+            watch.exited = true;
+            if let ReplyTo::Sampler(sampler_id) = reply_to {
+                stop_sampler = Some(sampler_id);
+            }
+        }
+        let result = ProcessUsageResult {
+            pid,
+            name,
+            exited: true,
+            usage: None,
+        };
+        let result = serde_json::to_value(result)
+            .context("Failed to serialize process result.")
+            .map_err(PcUsageError::UnexpectedError);
+        self.reply(reply_to, result);
+        if let Some(sampler_id) = stop_sampler {
+            self.stop_sampler(sampler_id, ctx);
+        }
+    }
+}
+
+impl Handler<GetProcessUsage> for PcUsageSystem {
+    type Result = ();
+
+    #[tracing::instrument(name = "Handle task GetProcessUsage", skip(self, ctx))]
+    fn handle(&mut self, message: GetProcessUsage, ctx: &mut Self::Context) -> Self::Result {
+        let GetProcessUsage { reply_to, target } = message;
+        let root = self.settings.procfs_root.clone();
+        let resolved = target.clone();
+        read_blocking(move || {
+            let pid = resolved.resolve(&root)?;
+            let (cpu_ticks, cpus) = procfs::cpu_ticks(&root)?;
+            let process = procfs::read_process(&root, pid)?;
+            Ok((cpu_ticks, cpus, process))
+        })
+        .into_actor(self)
+        .map(|first, act, ctx| act.measure_process(reply_to, target, first, ctx))
+        .spawn(ctx);
+    }
+}
+
+impl PcUsageSystem {
+    /// Measures the usage of a process over `PROCESS_CPU_WINDOW`, given the
+    /// total cpu ticks, the number of cpus and the process read at the start.
+    fn measure_process(
+        &mut self,
+        reply_to: ReplyTo,
+        target: ProcessTarget,
+        first: Result<(u64, usize, procfs::Process), PcUsageError>,
+        ctx: &mut <Self as Actor>::Context,
+    ) {
+        let (cpu_ticks, cpus, first) = match first {
+            Ok(first) => first,
+            Err(e) => {
+                match (&target, e) {
+                    // The subscribed process is gone
+                    (ProcessTarget::Pid { pid }, PcUsageError::UnknownProcess(_))
+                        if self.process_watch(&reply_to, *pid).is_some() =>
+                    {
+                        self.process_exited(reply_to, *pid, None, ctx)
+                    }
+                    (_, e) => self.reply(reply_to, Err(e)),
+                }
+                return;
+            }
+        };
+
+        ctx.run_later(PROCESS_CPU_WINDOW, move |act, ctx| {
+            let root = act.settings.procfs_root.clone();
+            let pid = first.pid;
+            let first_ticks = first.cpu_ticks;
+            read_blocking(move || {
+                let (total, _) = procfs::cpu_ticks(&root)?;
+                let second = procfs::read_process(&root, pid)?;
+                let io = procfs::process_io(&root, pid);
+                Ok::<_, anyhow::Error>(ProcessUsageResult {
+                    pid,
+                    name: Some(second.name),
+                    exited: false,
+                    usage: Some(ProcessUsage {
+                        cpu: cpu_percent(
+                            second.cpu_ticks.saturating_sub(first_ticks),
+                            total.saturating_sub(cpu_ticks),
+                            cpus,
+                        ),
+                        rss: second.rss,
+                        threads: second.threads,
+                        open_fds: procfs::open_fds(&root, pid),
+                        read_bytes: io.as_ref().map(|o| o.read_bytes),
+                        write_bytes: io.map(|o| o.write_bytes),
+                    }),
+                })
+            })
+            .into_actor(act)
+            .map(move |result, act, ctx| {
+                let result = match result {
+                    Ok(result) => result,
+                    Err(_) => return act.process_exited(reply_to, pid, Some(first.name), ctx),
+                };
+                let result = serde_json::to_value(result)
+                    .context("Failed to serialize process result.")
+                    .map_err(PcUsageError::UnexpectedError);
+                act.reply(reply_to, result);
+            })
+            .spawn(ctx);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn top_processes_sorts_and_limits() {
+        let process = |pid, cpu, rss| ProcessResult {
+            pid,
+            name: pid.to_string(),
+            cmdline: String::new(),
+            user: None,
+            cpu,
+            rss,
+            state: "S".into(),
+        };
+        let processes = vec![
+            process(1, Some(5.0), 300),
+            process(2, Some(50.0), 100),
+            process(3, Some(1.0), 200),
+            process(4, None, 50),
+        ];
+        let pids = |o: Vec<ProcessResult>| o.into_iter().map(|o| o.pid).collect::<Vec<_>>();
+        assert_eq!(
+            vec![2, 1],
+            pids(top_processes(processes.clone(), ProcessSortKey::Cpu, 2))
+        );
+        assert_eq!(
+            vec![2, 1, 3, 4],
+            pids(top_processes(processes.clone(), ProcessSortKey::Cpu, 10))
+        );
+        assert_eq!(
+            vec![1, 3, 2, 4],
+            pids(top_processes(processes, ProcessSortKey::Memory, 10))
+        );
+    }
+
+    #[test]
+    fn usage_since_has_no_cpu_for_new_processes() {
+        let process = |pid, cpu_ticks| procfs::Process {
+            pid,
+            name: pid.to_string(),
+            cmdline: String::new(),
+            state: 'S',
+            uid: None,
+            cpu_ticks,
+            rss: 0,
+            threads: 1,
+        };
+        let previous = ProcessSample {
+            cpu_ticks: 1000,
+            cpus: 2,
+            processes: vec![process(1, 100)],
+        };
+        let current = ProcessSample {
+            cpu_ticks: 1200,
+            cpus: 2,
+            processes: vec![process(1, 150), process(2, 5000)],
+        };
+        let usage = current.usage_since(&previous);
+        assert_eq!(Some(50.0), usage[0].cpu);
+        assert_eq!(None, usage[1].cpu);
+    }
+}
@@ -0,0 +1,220 @@
+//! Background samples, their history and the alerts evaluated on them.
+use super::{CpuLoadResult, PcUsageError, PcUsageSystem};
+use crate::websocket::{
+    alerts::{Alert, AlertRule},
+    history::{now_millis, HistorySample},
+    message::RequestId,
+    subsystem::WebsocketSubSystem,
+};
+use actix::{Handler, Message};
+use anyhow::Context;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+impl PcUsageSystem {
+    /// Adds a sample to `history` and evaluates alerts, the cpu load is measured
+    /// since the previous sample.
+    pub(super) fn sample(&mut self) {
+        // Nothing consumes the samples
+        if self.settings.history_size == 0 && self.alerts.is_empty() {
+            self.sampler_cpu = None;
+            self.latest_sample = None;
+            return;
+        }
+        let cpu = self.sampler_cpu.take().map(|measurement| {
+            measurement().map(|cores| {
+                let load = CpuLoadResult::aggregate(&cores);
+                (1.0 - load.idle, load.user)
+            })
+        });
+        self.sampler_cpu = match self.metrics.cpu_load() {
+            Ok(measurement) => Some(measurement),
+            Err(e) => {
+                tracing::error!("Failed to start cpu measurement: {:?}", e);
+                None
+            }
+        };
+        // The first sample only starts the cpu measurement
+        let (cpu, cpu_user) = match cpu {
+            Some(Ok(cpu)) => cpu,
+            Some(Err(e)) => {
+                tracing::error!("Failed to read cpu load: {:?}", e);
+                return;
+            }
+            None => return,
+        };
+        let memory = match self.metrics.memory() {
+            Ok(memory) => memory.memory,
+            Err(e) => {
+                tracing::error!("{:?}", e);
+                return;
+            }
+        };
+        let sample = HistorySample {
+            timestamp: now_millis(),
+            cpu,
+            cpu_user,
+            memory_used: memory.used(),
+            memory_total: memory.total,
+        };
+        self.evaluate_alerts(&sample);
+        self.latest_sample = Some(sample.clone());
+        self.history.push(sample);
+    }
+
+    fn evaluate_alerts(&mut self, sample: &HistorySample) {
+        let mut events = Vec::new();
+        for (id, alerts) in self.alerts.iter_mut() {
+            for alert in alerts.iter_mut() {
+                if let Some(event) = alert.evaluate(sample) {
+                    events.push((*id, alert.request_id.clone(), event));
+                }
+            }
+        }
+        for (id, request_id, event) in events {
+            let result = serde_json::to_value(event)
+                .context("Failed to serialize alert event.")
+                .map_err(PcUsageError::UnexpectedError);
+            self.send_message(id, request_id, result);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
+pub struct HistoryPayload {
+    /// In milliseconds since the unix epoch
+    #[serde(default)]
+    pub from: Option<u64>,
+    /// In milliseconds since the unix epoch
+    #[serde(default)]
+    pub to: Option<u64>,
+    /// Averages consecutive samples so no more than `max_points` are sent.
+    #[serde(default)]
+    pub max_points: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
+pub struct RemoveAlertPayload {
+    /// Id returned by `add_alert`.
+    pub id: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct RemoveAlertResult {
+    pub removed: u64,
+}
+
+#[derive(Debug, Message)]
+#[rtype(result = "()")]
+pub struct GetHistory {
+    pub(super) id: Uuid,
+    pub(super) request_id: RequestId,
+    pub(super) query: HistoryPayload,
+}
+
+impl Handler<GetHistory> for PcUsageSystem {
+    type Result = ();
+
+    #[tracing::instrument(name = "Handle task GetHistory", skip(self, _ctx))]
+    fn handle(&mut self, message: GetHistory, _ctx: &mut Self::Context) -> Self::Result {
+        let HistoryPayload {
+            from,
+            to,
+            max_points,
+        } = message.query;
+        let samples = self.history.query(from, to, max_points);
+        let result = serde_json::to_value(samples)
+            .context("Failed to serialize history.")
+            .map_err(PcUsageError::UnexpectedError);
+        self.send_message(message.id, message.request_id, result);
+    }
+}
+
+#[derive(Debug, Message)]
+#[rtype(result = "()")]
+pub struct AddAlert {
+    pub(super) id: Uuid,
+    pub(super) request_id: RequestId,
+    pub(super) rule: AlertRule,
+}
+
+#[derive(Debug, Message)]
+#[rtype(result = "()")]
+pub struct ListAlerts {
+    pub(super) id: Uuid,
+    pub(super) request_id: RequestId,
+}
+
+#[derive(Debug, Message)]
+#[rtype(result = "()")]
+pub struct RemoveAlert {
+    pub(super) id: Uuid,
+    pub(super) request_id: RequestId,
+    pub(super) alert_id: u64,
+}
+
+impl Handler<AddAlert> for PcUsageSystem {
+    type Result = ();
+
+    #[tracing::instrument(name = "Handle task AddAlert", skip(self, _ctx))]
+    fn handle(&mut self, message: AddAlert, _ctx: &mut Self::Context) -> Self::Result {
+        let alert = Alert::new(
+            self.next_alert_id,
+            message.rule,
+            message.request_id.pushed(),
+        );
+        self.next_alert_id += 1;
+        let result = serde_json::to_value(alert.info())
+            .context("Failed to serialize alert.")
+            .map_err(PcUsageError::UnexpectedError);
+        self.alerts.entry(message.id).or_default().push(alert);
+        self.send_message(message.id, message.request_id, result);
+    }
+}
+
+impl Handler<ListAlerts> for PcUsageSystem {
+    type Result = ();
+
+    #[tracing::instrument(name = "Handle task ListAlerts", skip(self, _ctx))]
+    fn handle(&mut self, message: ListAlerts, _ctx: &mut Self::Context) -> Self::Result {
+        let alerts = self
+            .alerts
+            .get(&message.id)
+            .map(|alerts| alerts.iter().map(Alert::info).collect::<Vec<_>>())
+            .unwrap_or_default();
+        let result = serde_json::to_value(alerts)
+            .context("Failed to serialize alerts.")
+            .map_err(PcUsageError::UnexpectedError);
+        self.send_message(message.id, message.request_id, result);
+    }
+}
+
+impl Handler<RemoveAlert> for PcUsageSystem {
+    type Result = ();
+
+    #[tracing::instrument(name = "Handle task RemoveAlert", skip(self, _ctx))]
+    fn handle(&mut self, message: RemoveAlert, _ctx: &mut Self::Context) -> Self::Result {
+        let alerts = self.alerts.get_mut(&message.id);
+        let position = alerts
+            .as_ref()
+            .and_then(|o| o.iter().position(|o| o.id == message.alert_id));
+        let result = match (alerts, position) {
+            (Some(alerts), Some(i)) => {
+                alerts.remove(i);
+                // Lets the sampler go idle once no alert is left
+                if alerts.is_empty() {
+                    self.alerts.remove(&message.id);
+                }
+                let result = RemoveAlertResult {
+                    removed: message.alert_id,
+                };
+                serde_json::to_value(result)
+                    .context("Failed to serialize remove alert result.")
+                    .map_err(PcUsageError::UnexpectedError)
+            }
+            _ => Err(PcUsageError::UnknownAlert(message.alert_id)),
+        };
+        self.send_message(message.id, message.request_id, result);
+    }
+}
@@ -0,0 +1,282 @@
+//! Subscriptions, served by samplers shared among the sessions subscribed to
+//! the same task and interval.
+use super::{PcUsageError, PcUsageSystem, ProcessTarget, ProcessWatch, ReplyTo, Tasks};
+use crate::websocket::{
+    message::{ClientMessage, RequestId},
+    subsystem::WebsocketSubSystem,
+};
+use actix::{Actor, AsyncContext, Handler, Message, SpawnHandle};
+use anyhow::Context;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DurationMilliSeconds};
+use std::{collections::HashMap, time::Duration};
+use uuid::Uuid;
+
+/// Minimum interval allowed for subscriptions.
+const MIN_SUBSCRIPTION_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Time a sampler without subscribers is kept paused before being dropped, so it
+/// can be resumed and its measurements in flight are discarded quietly.
+const PAUSED_SAMPLER_TTL: Duration = Duration::from_secs(1);
+
+/// Runs a subscribed task on every interval, sharing each result among the
+/// sessions subscribed to the same task and interval.
+#[derive(Debug)]
+pub(super) struct Sampler {
+    task: Tasks,
+    interval: Duration,
+    /// `None` while paused, i.e. nobody is subscribed.
+    handle: Option<SpawnHandle>,
+    /// `request_id` of the subscription of each session.
+    pub(super) subscribers: HashMap<Uuid, RequestId>,
+}
+
+#[serde_as]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, JsonSchema)]
+pub struct SubscribePayload {
+    /// Task to run on every interval, given as `task` and `payload`.
+    #[serde(flatten)]
+    pub task: Box<Tasks>,
+    /// In milliseconds
+    #[serde_as(as = "DurationMilliSeconds<u64>")]
+    #[schemars(with = "u64")]
+    pub interval: Duration,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct SubscribeResult {
+    /// Name of the subscribed task.
+    pub subscribed: String,
+    /// In milliseconds
+    pub interval: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, JsonSchema)]
+pub struct UnsubscribePayload {
+    /// Name of the subscribed task.
+    pub task: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct UnsubscribeResult {
+    /// Name of the task no longer subscribed.
+    pub unsubscribed: String,
+}
+
+/// Periodically runs `task` and sends each result to the client.
+#[derive(Debug, Message)]
+#[rtype(result = "()")]
+pub struct Subscribe {
+    pub(super) id: Uuid,
+    pub(super) request_id: RequestId,
+    pub(super) task: Tasks,
+    pub(super) interval: Duration,
+}
+
+/// Stops a subscription previously created with `Subscribe`.
+#[derive(Debug, Message)]
+#[rtype(result = "()")]
+pub struct Unsubscribe {
+    pub(super) id: Uuid,
+    pub(super) request_id: RequestId,
+    pub(super) task: String,
+}
+
+impl Handler<Subscribe> for PcUsageSystem {
+    type Result = ();
+
+    #[tracing::instrument(name = "Handle task Subscribe", skip(self, ctx))]
+    fn handle(&mut self, message: Subscribe, ctx: &mut Self::Context) -> Self::Result {
+        let Subscribe {
+            id,
+            request_id,
+            mut task,
+            interval,
+        } = message;
+        let name = task.name();
+
+        if !task.is_streamable() {
+            let e = PcUsageError::InvalidSubscription(format!(
+                "task {:?} can't be subscribed to.",
+                name
+            ));
+            self.send_error(id, request_id, &e);
+            return;
+        }
+        if interval < MIN_SUBSCRIPTION_INTERVAL {
+            let e = PcUsageError::InvalidSubscription(format!(
+                "interval should be at least {}ms.",
+                MIN_SUBSCRIPTION_INTERVAL.as_millis()
+            ));
+            self.send_error(id, request_id, &e);
+            return;
+        }
+        // Watch the same process on every interval, even if the target is a name
+        let mut watched_pid = None;
+        if let Tasks::Process(target) = &task {
+            match target.resolve(&self.settings.procfs_root) {
+                Ok(pid) => {
+                    watched_pid = Some(pid);
+                    task = Tasks::Process(ProcessTarget::Pid { pid });
+                }
+                Err(e) => {
+                    self.send_error(id, request_id, &e);
+                    return;
+                }
+            }
+        }
+
+        self.unsubscribe(id, &name, ctx);
+        let sampler_id = self.sampler_for(task, interval);
+        if let Some(pid) = watched_pid {
+            self.process_watches
+                .entry(sampler_id)
+                .or_insert(ProcessWatch { pid, exited: false });
+        }
+        if let Some(sampler) = self.samplers.get_mut(&sampler_id) {
+            sampler.subscribers.insert(id, request_id.pushed());
+        }
+        self.subscriptions.insert((id, name.clone()), sampler_id);
+        self.resume_sampler(sampler_id, ctx);
+
+        let result = SubscribeResult {
+            subscribed: name,
+            interval: interval.as_millis() as u64,
+        };
+        let result = serde_json::to_value(result)
+            .context("Failed to serialize subscribe result.")
+            .map_err(PcUsageError::UnexpectedError);
+        self.send_message(id, request_id, result);
+    }
+}
+
+impl PcUsageSystem {
+    /// Id of the sampler running `task` on `interval`, creating a paused one if needed.
+    fn sampler_for(&mut self, task: Tasks, interval: Duration) -> Uuid {
+        let existing = self.samplers.iter().find(|(sampler_id, sampler)| {
+            let exited = self
+                .process_watches
+                .get(sampler_id)
+                .map(|o| o.exited)
+                .unwrap_or(false);
+            sampler.task == task && sampler.interval == interval && !exited
+        });
+        if let Some((sampler_id, _)) = existing {
+            return *sampler_id;
+        }
+        let sampler_id = Uuid::new_v4();
+        let sampler = Sampler {
+            task,
+            interval,
+            handle: None,
+            subscribers: Default::default(),
+        };
+        self.samplers.insert(sampler_id, sampler);
+        sampler_id
+    }
+
+    fn resume_sampler(&mut self, sampler_id: Uuid, ctx: &mut <Self as Actor>::Context) {
+        let sampler = match self.samplers.get_mut(&sampler_id) {
+            Some(sampler) if sampler.handle.is_none() => sampler,
+            _ => return,
+        };
+        let handle = ctx.run_interval(sampler.interval, move |act, ctx| {
+            // Measurements can outlast the interval, overlapping them would
+            // send results out of order
+            if !act.measuring.insert(sampler_id) {
+                return;
+            }
+            if let Some(sampler) = act.samplers.get(&sampler_id) {
+                act.measure(ReplyTo::Sampler(sampler_id), sampler.task.clone(), ctx);
+            }
+        });
+        sampler.handle = Some(handle);
+    }
+
+    /// Sends the result of a sampler measurement to each of its subscribers.
+    pub(super) fn publish(&mut self, sampler_id: Uuid, message: ClientMessage) {
+        self.measuring.remove(&sampler_id);
+        let sampler = match self.samplers.get(&sampler_id) {
+            Some(sampler) => sampler,
+            None => return,
+        };
+        for (id, request_id) in sampler.subscribers.iter() {
+            if self.is_connected(id) {
+                self.deliver(*id, request_id.clone(), message.clone());
+            }
+        }
+    }
+
+    /// Stops sampling once nobody is subscribed, dropping the sampler unless it
+    /// gets resumed within `PAUSED_SAMPLER_TTL`.
+    fn pause_sampler(&mut self, sampler_id: Uuid, ctx: &mut <Self as Actor>::Context) {
+        let sampler = match self.samplers.get_mut(&sampler_id) {
+            Some(sampler) => sampler,
+            None => return,
+        };
+        if let Some(handle) = sampler.handle.take() {
+            ctx.cancel_future(handle);
+        }
+        ctx.run_later(PAUSED_SAMPLER_TTL, move |act, _ctx| {
+            let paused = act
+                .samplers
+                .get(&sampler_id)
+                .map(|o| o.handle.is_none())
+                .unwrap_or(false);
+            if paused {
+                act.samplers.remove(&sampler_id);
+                act.measuring.remove(&sampler_id);
+                act.process_watches.remove(&sampler_id);
+            }
+        });
+    }
+
+    /// Removes the subscription of session `id` to task `name`, returns whether
+    /// there was one.
+    pub(super) fn unsubscribe(
+        &mut self,
+        id: Uuid,
+        name: &str,
+        ctx: &mut <Self as Actor>::Context,
+    ) -> bool {
+        let sampler_id = match self.subscriptions.remove(&(id, name.to_string())) {
+            Some(sampler_id) => sampler_id,
+            None => return false,
+        };
+        if let Some(sampler) = self.samplers.get_mut(&sampler_id) {
+            sampler.subscribers.remove(&id);
+            if sampler.subscribers.is_empty() {
+                self.pause_sampler(sampler_id, ctx);
+            }
+        }
+        true
+    }
+
+    /// Ends every subscription served by a sampler.
+    pub(super) fn stop_sampler(&mut self, sampler_id: Uuid, ctx: &mut <Self as Actor>::Context) {
+        if let Some(sampler) = self.samplers.get_mut(&sampler_id) {
+            sampler.subscribers.clear();
+        }
+        self.subscriptions
+            .retain(|_, subscription| *subscription != sampler_id);
+        self.pause_sampler(sampler_id, ctx);
+    }
+}
+
+impl Handler<Unsubscribe> for PcUsageSystem {
+    type Result = ();
+
+    #[tracing::instrument(name = "Handle task Unsubscribe", skip(self, ctx))]
+    fn handle(&mut self, message: Unsubscribe, ctx: &mut Self::Context) -> Self::Result {
+        let result = if self.unsubscribe(message.id, &message.task, ctx) {
+            Ok(serde_json::json!({ "unsubscribed": message.task }))
+        } else {
+            Err(PcUsageError::InvalidSubscription(format!(
+                "no active subscription for task {:?}.",
+                message.task
+            )))
+        };
+        self.send_message(message.id, message.request_id, result);
+    }
+}
@@ -1,14 +1,21 @@
 use super::{
-    error::WebsocketError,
-    message::{ClientMessage, Connect, SubSystemPart, TaskMessage, TaskPayload, WebsocketSystems},
+    error::{ClientError, WebsocketError},
+    message::{
+        ClientMessage, Connect, Disconnect, RequestId, SessionCount, SubSystemPart, TaskMessage,
+    },
+    meta::{described_tasks, TaskDescription},
     subsystem::WebsocketSubSystem,
 };
 use crate::error_chain_fmt;
 use actix::{Actor, AsyncContext, Handler, Message, Recipient};
 use anyhow::Context;
 use glob::glob;
-use serde::Deserialize;
-use std::{collections::HashMap, convert::TryFrom, path::Path};
+use schemars::{gen::SchemaGenerator, schema::Schema, JsonSchema};
+use serde::{de, Deserialize, Deserializer, Serialize};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
 use uuid::Uuid;
 
 #[derive(thiserror::Error)]
@@ -16,6 +23,8 @@ pub enum PythonRepoError {
     #[error("Invalid path: {0:?}")]
     InvalidPath(String),
     #[error(transparent)]
+    WebsocketError(#[from] WebsocketError),
+    #[error(transparent)]
     UnexpectedError(#[from] anyhow::Error),
 }
 
@@ -25,34 +34,47 @@ impl std::fmt::Debug for PythonRepoError {
     }
 }
 
+impl ClientError for PythonRepoError {
+    fn code(&self) -> &'static str {
+        match self {
+            PythonRepoError::InvalidPath(_) => "invalid_path",
+            PythonRepoError::WebsocketError(e) => e.code(),
+            PythonRepoError::UnexpectedError(_) => "internal",
+        }
+    }
+
+    fn details(&self) -> serde_json::Value {
+        match self {
+            PythonRepoError::InvalidPath(path) => serde_json::json!({ "path": path }),
+            PythonRepoError::WebsocketError(e) => e.details(),
+            _ => serde_json::Value::Null,
+        }
+    }
+}
+
 impl SubSystemPart for Result<serde_json::Value, PythonRepoError> {
-    fn system(&self) -> Option<WebsocketSystems> {
-        Some(WebsocketSystems::PythonRepo)
+    fn system(&self) -> Option<String> {
+        Some(PythonRepoSystem::NAME.into())
     }
 }
 
+#[derive(Default)]
 pub struct PythonRepoSystem {
     sessions: HashMap<Uuid, Recipient<ClientMessage>>,
 }
 
-impl Default for PythonRepoSystem {
-    fn default() -> Self {
-        Self {
-            sessions: Default::default(),
-        }
-    }
-}
-
 impl WebsocketSubSystem for PythonRepoSystem {
     type Error = PythonRepoError;
     type Task = Tasks;
 
+    const NAME: &'static str = "python_repo";
+
     fn get_address(&self, id: &Uuid) -> Option<&Recipient<ClientMessage>> {
         self.sessions.get(id)
     }
 
-    fn system(&self) -> WebsocketSystems {
-        WebsocketSystems::PythonRepo
+    fn tasks() -> Vec<TaskDescription> {
+        Tasks::descriptions()
     }
 }
 
@@ -69,64 +91,120 @@ impl Handler<Connect> for PythonRepoSystem {
     }
 }
 
+impl Handler<Disconnect> for PythonRepoSystem {
+    type Result = ();
+
+    #[tracing::instrument(name = "Disconnecting socket from PythonRepoSystem", skip(self, _ctx))]
+    fn handle(&mut self, message: Disconnect, _ctx: &mut Self::Context) -> Self::Result {
+        self.sessions.remove(&message.id);
+    }
+}
+
+impl Handler<SessionCount> for PythonRepoSystem {
+    type Result = usize;
+
+    fn handle(&mut self, _message: SessionCount, _ctx: &mut Self::Context) -> Self::Result {
+        self.sessions.len()
+    }
+}
+
 /// Dispatcher for task handlers
 impl Handler<TaskMessage> for PythonRepoSystem {
     type Result = ();
 
     #[tracing::instrument(name = "Handle task (PythonRepoSystem)", skip(self, ctx))]
     fn handle(&mut self, task_message: TaskMessage, ctx: &mut Self::Context) -> Self::Result {
-        let task = match self.task_from_message(task_message.clone()) {
+        let task = match self.task_from_message(&task_message) {
             Ok(task) => task,
             Err(_) => return,
         };
 
         let addr = ctx.address();
+        let payload = task_message.payload;
         match task {
-            Tasks::GetFiles => {
-                let _ = GetFiles::try_from(task_message.payload).map(|task| addr.do_send(task));
-            }
+            Tasks::GetFiles(data) => addr.do_send(GetFiles {
+                id: payload.id,
+                request_id: payload.request_id,
+                path: data.path,
+            }),
+        }
+    }
+}
+
+described_tasks! {
+    #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
+    #[serde(tag = "task", content = "payload", rename_all = "snake_case")]
+    pub enum Tasks {
+        /// Python files found recursively under a path.
+        GetFiles(GetFilesPayload) => Vec<PathBuf>,
+    }
+}
+
+/// Also accepted as a bare string with the path, as sent by older clients.
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct GetFilesPayload {
+    /// Directory to search for python files.
+    pub path: String,
+}
+
+impl<'de> Deserialize<'de> for GetFilesPayload {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        struct Payload {
+            path: String,
+        }
+
+        match serde_json::Value::deserialize(deserializer)? {
+            serde_json::Value::String(path) => Ok(Self { path }),
+            // Parsed on its own so errors still name the missing fields
+            value => Payload::deserialize(value)
+                .map(|o| Self { path: o.path })
+                .map_err(de::Error::custom),
         }
     }
 }
 
-#[derive(Debug, Deserialize, PartialEq, Eq)]
-#[serde(rename_all = "snake_case")]
-pub enum Tasks {
-    GetFiles,
+/// Forms accepted for `GetFilesPayload`, only used to describe its schema.
+#[derive(JsonSchema)]
+#[serde(untagged)]
+#[allow(dead_code)]
+enum GetFilesPayloadForm {
+    Path(String),
+    Object {
+        /// Directory to search for python files.
+        path: String,
+    },
+}
+
+impl JsonSchema for GetFilesPayload {
+    fn schema_name() -> String {
+        "GetFilesPayload".into()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        GetFilesPayloadForm::json_schema(gen)
+    }
 }
 
 #[derive(Debug, Message)]
 #[rtype(result = "()")]
 pub struct GetFiles {
     id: Uuid,
+    request_id: RequestId,
     path: String,
 }
 
-impl TryFrom<TaskPayload> for GetFiles {
-    type Error = WebsocketError;
-
-    fn try_from(payload: TaskPayload) -> Result<Self, Self::Error> {
-        let path = payload
-            .data
-            .as_str()
-            .ok_or_else(|| {
-                WebsocketError::MessageParseError(anyhow::anyhow!("No `path` found on payload."))
-            })?
-            .into();
-        Ok(Self {
-            id: payload.id,
-            path,
-        })
-    }
-}
-
 impl Handler<GetFiles> for PythonRepoSystem {
     type Result = ();
 
     #[tracing::instrument(name = "Handle task GetFiles", skip(self, _ctx))]
     fn handle(&mut self, message: GetFiles, _ctx: &mut Self::Context) -> Self::Result {
         if !Path::new(&message.path).exists() {
-            self.send_message(message.id, Err(PythonRepoError::InvalidPath(message.path)));
+            self.send_message(
+                message.id,
+                message.request_id,
+                Err(PythonRepoError::InvalidPath(message.path)),
+            );
             return;
         }
 
@@ -134,31 +212,88 @@ impl Handler<GetFiles> for PythonRepoSystem {
             .context("Failed to perform glob on path.")
         {
             Ok(files) => {
-                let files = files.filter_map(Result::ok).collect::<Vec<_>>();
+                let files = files.filter_map(Result::ok).collect::<Vec<PathBuf>>();
                 serde_json::to_value(files).context("Failed to convert message to JSON format.")
             }
             Err(e) => Err(e),
         }
         .map_err(PythonRepoError::UnexpectedError);
 
-        self.send_message(message.id, result);
+        self.send_message(message.id, message.request_id, result);
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::websocket::message::RawWebsocketMessage;
+    use crate::websocket::{message::WebsocketMessage, subsystem::parse_task};
+
+    fn parse(message: serde_json::Value) -> Result<Tasks, WebsocketError> {
+        let message = WebsocketMessage::parse(Uuid::new_v4(), &message.to_string()).unwrap();
+        parse_task::<Tasks>(&message.task)
+    }
+
+    #[test]
+    fn correctly_deserialize_task() {
+        let message = serde_json::json!({
+            "system": "python_repo",
+            "task": "get_files",
+            "payload": { "path": "tests/examples" }
+        });
+        let task = parse(message).unwrap();
+        assert_eq!(
+            Tasks::GetFiles(GetFilesPayload {
+                path: "tests/examples".into()
+            }),
+            task
+        );
+    }
 
     #[test]
-    fn correctly_deserialize_task_name() {
+    fn correctly_deserialize_legacy_string_payload() {
         let message = serde_json::json!({
             "system": "python_repo",
             "task": "get_files",
             "payload": "tests/examples"
         });
-        let message = serde_json::from_value::<RawWebsocketMessage>(message).unwrap();
-        let task = serde_json::from_str::<Tasks>(&format!("{:?}", message.task)).unwrap();
-        assert_eq!(Tasks::GetFiles, task);
+        let task = parse(message).unwrap();
+        assert_eq!(
+            Tasks::GetFiles(GetFilesPayload {
+                path: "tests/examples".into()
+            }),
+            task
+        );
+    }
+
+    #[test]
+    fn payload_schema_accepts_string_and_path_object() {
+        let schema = serde_json::to_value(schemars::schema_for!(GetFilesPayload)).unwrap();
+        let forms = schema["anyOf"].as_array().expect("Schema is not an anyOf.");
+        assert_eq!(forms.len(), 2, "{}", schema);
+        assert_eq!(forms[0]["type"], "string");
+        assert_eq!(forms[1]["required"], serde_json::json!(["path"]));
+    }
+
+    #[test]
+    fn invalid_payload_error_names_missing_field() {
+        let message = serde_json::json!({
+            "system": "python_repo",
+            "task": "get_files",
+            "payload": {}
+        });
+        let error = parse(message).unwrap_err();
+        assert!(matches!(error, WebsocketError::InvalidPayload(..)));
+        assert!(error.to_string().contains("`path`"), "{}", error);
+    }
+
+    #[test]
+    fn unknown_task_error_lists_tasks() {
+        let message = serde_json::json!({
+            "system": "python_repo",
+            "task": "invalid_task_name",
+        });
+        let error = parse(message).unwrap_err();
+        assert!(matches!(error, WebsocketError::UnknownTask(..)));
+        assert!(error.to_string().contains("get_files"), "{}", error);
     }
 }
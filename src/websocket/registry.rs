@@ -0,0 +1,64 @@
+use super::{
+    message::{Connect, Disconnect, SessionCount, TaskMessage},
+    meta::TaskDescription,
+    subsystem::WebsocketSubSystem,
+};
+use actix::{Actor, Addr, Handler, Recipient};
+use std::collections::BTreeMap;
+
+/// Recipients used to communicate with a registered subsystem.
+#[derive(Clone)]
+pub struct SubSystemHandle {
+    pub task: Recipient<TaskMessage>,
+    pub connect: Recipient<Connect>,
+    pub disconnect: Recipient<Disconnect>,
+    pub session_count: Recipient<SessionCount>,
+    pub tasks: Vec<TaskDescription>,
+    /// See `WebsocketSubSystem::inner_tasks`.
+    pub inner_tasks: fn(&TaskMessage) -> Vec<String>,
+}
+
+/// Subsystems available to websocket clients, keyed by system name.
+#[derive(Clone, Default)]
+pub struct SubSystemRegistry {
+    systems: BTreeMap<&'static str, SubSystemHandle>,
+}
+
+impl SubSystemRegistry {
+    /// Registers a subsystem under its `WebsocketSubSystem::NAME`.
+    pub fn register<A>(&mut self, addr: Addr<A>) -> &mut Self
+    where
+        A: WebsocketSubSystem
+            + Actor<Context = actix::Context<A>>
+            + Handler<TaskMessage>
+            + Handler<Connect>
+            + Handler<Disconnect>
+            + Handler<SessionCount>,
+    {
+        let handle = SubSystemHandle {
+            task: addr.clone().recipient(),
+            connect: addr.clone().recipient(),
+            disconnect: addr.clone().recipient(),
+            session_count: addr.recipient(),
+            tasks: A::tasks(),
+            inner_tasks: A::inner_tasks,
+        };
+        if self.systems.insert(A::NAME, handle).is_some() {
+            tracing::warn!("Subsystem {:?} was registered twice.", A::NAME);
+        }
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<&SubSystemHandle> {
+        self.systems.get(name)
+    }
+
+    /// Names of the registered subsystems, sorted alphabetically.
+    pub fn names(&self) -> Vec<&'static str> {
+        self.systems.keys().copied().collect()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&'static str, &SubSystemHandle)> {
+        self.systems.iter().map(|(name, handle)| (*name, handle))
+    }
+}
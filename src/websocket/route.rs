@@ -1,64 +1,151 @@
 use super::{
-    message::{ClientMessage, Connect, WebsocketMessage},
-    pc_usage::PcUsageSystem,
-    python_repo::PythonRepoSystem,
+    error::WebsocketError,
+    message::{
+        ClientMessage, ClientMessager, Connect, Disconnect, RawWebsocketMessage, RequestId,
+        WebsocketMessage,
+    },
+    registry::SubSystemRegistry,
+};
+use crate::{
+    authentication::{AuthError, Authenticator, Credentials, Identity},
+    authorization::Policy,
+    configuration::WebsocketSettings,
+    metrics::{ServerMetrics, UNKNOWN_LABEL, WEBSOCKET_LABEL},
 };
-use crate::{configuration::WebsocketSettings, websocket::message::WebsocketSystems};
 use actix::{
-    Actor, ActorContext, ActorFutureExt, Addr, AsyncContext, ContextFutureSpawner, Handler,
+    Actor, ActorContext, ActorFutureExt, AsyncContext, ContextFutureSpawner, Handler,
     StreamHandler, WrapFuture,
 };
 use actix_web::{web, HttpRequest, HttpResponse};
 use actix_web_actors::ws;
-use std::time::Instant;
+use anyhow::Context;
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
 use uuid::Uuid;
 
+/// Tasks without a reply after this long stop being tracked as pending.
+const PENDING_TASK_TTL: Duration = Duration::from_secs(60);
+
+/// Most tasks tracked as pending by a session, the oldest ones are dropped first.
+const MAX_PENDING_TASKS: usize = 256;
+
 #[tracing::instrument(
     name = "Starting web socket",
-    skip(req, stream, websocket_settings, python_repo_system, pc_usage_system)
+    skip(req, stream, websocket_settings, registry, authenticator, policy, metrics),
+    fields(identity=tracing::field::Empty)
 )]
 pub async fn ws_index(
     req: HttpRequest,
     stream: web::Payload,
     websocket_settings: web::Data<WebsocketSettings>,
-    python_repo_system: web::Data<Addr<PythonRepoSystem>>,
-    pc_usage_system: web::Data<Addr<PcUsageSystem>>,
+    registry: web::Data<SubSystemRegistry>,
+    authenticator: web::Data<Authenticator>,
+    policy: web::Data<Policy>,
+    metrics: web::Data<ServerMetrics>,
 ) -> Result<HttpResponse, actix_web::Error> {
-    let resp = ws::start(
-        WebsocketSystem::new(
-            websocket_settings.as_ref(),
-            python_repo_system.get_ref().clone(),
-            pc_usage_system.get_ref().clone(),
-        ),
-        &req,
-        stream,
+    let (identity, protocol) = authenticate(&req, &authenticator).map_err(|e| {
+        tracing::warn!("Rejected websocket handshake: {:?}", e);
+        e
+    })?;
+    tracing::Span::current().record("identity", tracing::field::display(&identity.name));
+
+    let actor = WebsocketSystem::new(
+        websocket_settings.as_ref(),
+        registry,
+        policy,
+        metrics,
+        identity,
     );
-    resp
+    match protocol {
+        // The protocol carrying the token must be echoed back to the client
+        Some(protocol) => ws::start_with_protocols(actor, &[protocol.as_str()], &req, stream),
+        None => ws::start(actor, &req, stream),
+    }
+}
+
+/// Finds the identity of the client, along with the `Sec-WebSocket-Protocol`
+/// used to send the credentials (if any).
+fn authenticate(
+    req: &HttpRequest,
+    authenticator: &Authenticator,
+) -> Result<(Identity, Option<String>), AuthError> {
+    if !authenticator.enabled() {
+        return Ok((Identity::anonymous(), None));
+    }
+    let credentials = Credentials::from_request(req).ok_or(AuthError::MissingCredentials)?;
+    let identity = authenticator.validate(&credentials)?;
+    Ok((identity, credentials.protocol))
 }
 
 struct WebsocketSystem {
     id: Uuid,
     hb: Instant,
     settings: WebsocketSettings,
-    python_repo_system: Addr<PythonRepoSystem>,
-    pc_usage_system: Addr<PcUsageSystem>,
+    registry: web::Data<SubSystemRegistry>,
+    policy: web::Data<Policy>,
+    metrics: web::Data<ServerMetrics>,
+    identity: Identity,
+    /// Tasks waiting for their first reply, by dispatch token (see
+    /// `RequestId`), along with their metric labels.
+    pending: HashMap<u64, PendingTask>,
+    next_dispatch: u64,
+}
+
+#[derive(Debug)]
+struct PendingTask {
+    system: String,
+    task: String,
+    sent: Instant,
 }
 
 impl WebsocketSystem {
     fn new(
         settings: &WebsocketSettings,
-        python_repo_system: Addr<PythonRepoSystem>,
-        pc_usage_system: Addr<PcUsageSystem>,
+        registry: web::Data<SubSystemRegistry>,
+        policy: web::Data<Policy>,
+        metrics: web::Data<ServerMetrics>,
+        identity: Identity,
     ) -> Self {
         Self {
             id: Uuid::new_v4(),
             hb: Instant::now(),
             settings: settings.clone(),
-            python_repo_system,
-            pc_usage_system,
+            registry,
+            policy,
+            metrics,
+            identity,
+            pending: Default::default(),
+            next_dispatch: 0,
         }
     }
 
+    /// Metric labels of a task, unregistered systems and tasks share the same label.
+    fn metric_labels(&self, system: &str, task: &str) -> (String, String) {
+        match self.registry.get(system) {
+            Some(handle) if handle.tasks.iter().any(|o| o.name == task) => {
+                (system.to_string(), task.to_string())
+            }
+            Some(_) => (system.to_string(), UNKNOWN_LABEL.to_string()),
+            None => (UNKNOWN_LABEL.to_string(), UNKNOWN_LABEL.to_string()),
+        }
+    }
+
+    fn authorize(&self, system: &str, task: &str) -> Result<(), WebsocketError> {
+        if self.policy.is_allowed(&self.identity, system, task) {
+            return Ok(());
+        }
+        tracing::warn!(
+            identity = %self.identity.name,
+            roles = ?self.identity.roles,
+            system = %system,
+            task = %task,
+            "Task denied by policy."
+        );
+        Err(WebsocketError::Forbidden(system.into(), task.into()))
+    }
+
     /// Sends ping to client every x seconds.
     /// Also checks heathbeats from client.
     fn hb(&self, ctx: &mut <Self as Actor>::Context) {
@@ -71,61 +158,145 @@ impl WebsocketSystem {
                 return;
             }
             ctx.ping(b"");
+            act.expire_pending();
         });
     }
 
-    #[tracing::instrument(name = "Process message", skip(self, ctx))]
-    fn process_message(&self, text: &str, ctx: &mut ws::WebsocketContext<WebsocketSystem>) {
-        match WebsocketMessage::parse(self.id, text) {
-            Ok(message) => match message.system {
-                WebsocketSystems::PythonRepo => self.python_repo_system.do_send(message.task),
-                WebsocketSystems::PcUsage => self.pc_usage_system.do_send(message.task),
-            },
+    #[tracing::instrument(
+        name = "Process message",
+        skip(self, ctx),
+        fields(identity=%self.identity.name)
+    )]
+    fn process_message(&mut self, text: &str, ctx: &mut ws::WebsocketContext<WebsocketSystem>) {
+        let mut labels = (UNKNOWN_LABEL.to_string(), UNKNOWN_LABEL.to_string());
+        let result = WebsocketMessage::parse(self.id, text).and_then(|mut message| {
+            labels = self.metric_labels(&message.system, &message.task.name);
+            let system = self.registry.get(&message.system).ok_or_else(|| {
+                let available = self
+                    .registry
+                    .names()
+                    .into_iter()
+                    .map(String::from)
+                    .collect();
+                WebsocketError::UnknownSystem(message.system.clone(), available)
+            })?;
+            self.authorize(&message.system, &message.task.name)?;
+            for task in (system.inner_tasks)(&message.task) {
+                self.authorize(&message.system, &task)?;
+            }
+            self.next_dispatch += 1;
+            let dispatch = self.next_dispatch;
+            message.task.payload.request_id.dispatch = Some(dispatch);
+            let name = message.system;
+            system
+                .task
+                .do_send(message.task)
+                .with_context(|| format!("Failed to send task to {:?}.", name))?;
+            Ok(dispatch)
+        });
+
+        let (system, task) = labels;
+        match result {
+            Ok(dispatch) => {
+                self.metrics.task_dispatched(&system, &task);
+                let pending = PendingTask {
+                    system,
+                    task,
+                    sent: Instant::now(),
+                };
+                self.track_pending(dispatch, pending);
+            }
             Err(e) => {
+                self.metrics.task_failed(&system, &task);
                 tracing::error!("{:?}", e);
-                ctx.address().do_send(e.into());
+                let request_id = RequestId::new(RawWebsocketMessage::request_id(text));
+                let message = Err::<serde_json::Value, WebsocketError>(e).to_message(request_id);
+                ctx.address().do_send(message);
             }
         }
     }
 }
 
+impl WebsocketSystem {
+    fn track_pending(&mut self, dispatch: u64, task: PendingTask) {
+        if self.pending.len() >= MAX_PENDING_TASKS {
+            // Dispatch tokens only grow, so the lowest one is the oldest
+            if let Some(oldest) = self.pending.keys().min().copied() {
+                self.pending.remove(&oldest);
+            }
+        }
+        self.pending.insert(dispatch, task);
+    }
+
+    /// Stops tracking the tasks that never replied, such as the ones whose
+    /// subsystem dropped them.
+    fn expire_pending(&mut self) {
+        self.pending
+            .retain(|_, task| task.sent.elapsed() < PENDING_TASK_TTL);
+    }
+
+    /// Updates the metrics of the task `message` replies to, if any.
+    fn record_reply(&mut self, message: &ClientMessage) {
+        let system = match &message.system {
+            Some(system) => system,
+            None => {
+                self.metrics.message_sent(WEBSOCKET_LABEL);
+                return;
+            }
+        };
+        // Pushed messages and later replies of a task don't match a pending task
+        let pending = message.dispatch.and_then(|o| self.pending.remove(&o));
+        let pending = match pending {
+            Some(pending) => pending,
+            None => {
+                self.metrics.message_sent(system);
+                return;
+            }
+        };
+        self.metrics.message_sent(&pending.system);
+        self.metrics
+            .task_answered(&pending.system, &pending.task, pending.sent.elapsed());
+        if !message.success {
+            self.metrics.task_failed(&pending.system, &pending.task);
+        }
+    }
+}
+
 impl Actor for WebsocketSystem {
     type Context = ws::WebsocketContext<Self>;
 
     fn started(&mut self, ctx: &mut Self::Context) {
         self.hb(ctx);
+        self.metrics.session_opened();
 
-        // Register to PythonRepoSystem
-        self.python_repo_system
-            .send(Connect {
-                id: self.id,
-                addr: ctx.address().recipient(),
-            })
-            .into_actor(self)
-            .then(|res, _act, ctx| {
-                if let Err(e) = res {
-                    tracing::error!("Failed to connect to PythonRepoSystem: {:?}", e);
-                    ctx.stop();
-                }
-                actix::fut::ready(())
-            })
-            .wait(ctx);
-
-        // Register to PythonRepoSystem
-        self.pc_usage_system
-            .send(Connect {
-                id: self.id,
-                addr: ctx.address().recipient(),
-            })
-            .into_actor(self)
-            .then(|res, _act, ctx| {
-                if let Err(e) = res {
-                    tracing::error!("Failed to connect to PcUsageSystem: {:?}", e);
-                    ctx.stop();
-                }
-                actix::fut::ready(())
-            })
-            .wait(ctx);
+        // Register to every subsystem
+        for (name, system) in self.registry.iter() {
+            system
+                .connect
+                .send(Connect {
+                    id: self.id,
+                    addr: ctx.address().recipient(),
+                })
+                .into_actor(self)
+                .then(move |res, _act, ctx| {
+                    if let Err(e) = res {
+                        tracing::error!("Failed to connect to {:?}: {:?}", name, e);
+                        ctx.stop();
+                    }
+                    actix::fut::ready(())
+                })
+                .wait(ctx);
+        }
+    }
+
+    fn stopped(&mut self, _ctx: &mut Self::Context) {
+        self.metrics.session_closed();
+        // Let the subsystems drop everything tied to this session
+        for (name, system) in self.registry.iter() {
+            if let Err(e) = system.disconnect.do_send(Disconnect { id: self.id }) {
+                tracing::error!("Failed to disconnect from {:?}: {:?}", name, e);
+            }
+        }
     }
 }
 
@@ -133,7 +304,7 @@ impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for WebsocketSystem {
     #[tracing::instrument(
         name = "Handling websocket message",
         skip(self, item, ctx),
-        fields(message=tracing::field::Empty)
+        fields(message=tracing::field::Empty, identity=%self.identity.name)
     )]
     fn handle(&mut self, item: Result<ws::Message, ws::ProtocolError>, ctx: &mut Self::Context) {
         let msg = match item {
@@ -144,7 +315,7 @@ impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for WebsocketSystem {
                 return;
             }
         };
-        tracing::Span::current().record("message", &tracing::field::debug(&msg));
+        tracing::Span::current().record("message", tracing::field::debug(&msg));
 
         match msg {
             ws::Message::Ping(msg) => {
@@ -174,6 +345,7 @@ impl Handler<ClientMessage> for WebsocketSystem {
 
     #[tracing::instrument(name = "Redirecting message to client", skip(self, ctx))]
     fn handle(&mut self, message: ClientMessage, ctx: &mut Self::Context) -> Self::Result {
+        self.record_reply(&message);
         match serde_json::to_string(&message) {
             Ok(message) => ctx.text(message),
             Err(e) => tracing::error!("Failed to send message to client: {:?}", e),
@@ -1,13 +1,26 @@
-use super::message::{ClientMessage, ClientMessager, TaskMessage, WebsocketSystems};
+use super::{
+    error::{ClientError, WebsocketError},
+    message::{ClientMessage, ClientMessager, RequestId, TaskMessage},
+    meta::TaskDescription,
+};
 use actix::Recipient;
-use anyhow::Context;
+use serde::de::{self, value::MapDeserializer, DeserializeOwned};
 use uuid::Uuid;
 
 pub trait WebsocketSubSystem {
     type Error;
     type Task;
 
-    fn system(&self) -> WebsocketSystems;
+    /// Name used by clients to address this subsystem.
+    const NAME: &'static str;
+
+    /// Descriptions of the tasks accepted by this subsystem.
+    fn tasks() -> Vec<TaskDescription>;
+
+    /// Tasks run on behalf of `task`, clients must be allowed to run them too.
+    fn inner_tasks(_task: &TaskMessage) -> Vec<String> {
+        Vec::new()
+    }
 
     fn get_address(&self, id: &Uuid) -> Option<&Recipient<ClientMessage>>;
 
@@ -16,27 +29,20 @@ pub trait WebsocketSubSystem {
 		skip(self),
 		fields(subsystem=tracing::field::Empty)
 	)]
-    fn send_error(&self, id: Uuid, e: &Self::Error)
+    fn send_error(&self, id: Uuid, request_id: RequestId, e: &Self::Error)
     where
-        Self::Error: std::error::Error,
+        Self::Error: ClientError,
     {
         let type_name = std::any::type_name::<Self>();
-        tracing::Span::current().record("subsystem", &tracing::field::debug(type_name));
-        match self.get_address(&id) {
-            Some(addr) => {
-                let message = ClientMessage {
-                    system: Some(self.system()),
-                    success: false,
-                    payload: e.to_string().into(),
-                };
-                if let Err(e) = addr.do_send(message) {
-                    tracing::error!("Failed to send message from PythonRepoSystem: {:?}", e);
-                }
-            }
-            None => {
-                tracing::error!("No address found for id: {:?}", id);
-            }
-        }
+        tracing::Span::current().record("subsystem", tracing::field::debug(type_name));
+        let message = ClientMessage {
+            system: Some(Self::NAME.into()),
+            request_id: None,
+            success: false,
+            payload: e.to_payload().into(),
+            dispatch: None,
+        };
+        self.deliver(id, request_id, message);
     }
 
     #[tracing::instrument(
@@ -44,41 +50,107 @@ pub trait WebsocketSubSystem {
 		skip(self),
 		fields(subsystem=tracing::field::Empty)
 	)]
-    fn send_message(&self, id: Uuid, msg: Result<serde_json::Value, Self::Error>)
-    where
+    fn send_message(
+        &self,
+        id: Uuid,
+        request_id: RequestId,
+        msg: Result<serde_json::Value, Self::Error>,
+    ) where
         Result<serde_json::Value, Self::Error>: ClientMessager,
-        Self::Error: std::error::Error,
+        Self::Error: ClientError,
     {
         let type_name = std::any::type_name::<Self>();
-        tracing::Span::current().record("subsystem", &tracing::field::debug(type_name));
-        match self.get_address(&id) {
-            Some(addr) => {
-                let message = msg.to_message();
-                if let Err(e) = addr.do_send(message) {
-                    tracing::error!("Failed to send message from PythonRepoSystem: {:?}", e);
-                }
-            }
+        tracing::Span::current().record("subsystem", tracing::field::debug(type_name));
+        self.deliver(id, request_id, msg.to_message(RequestId::default()));
+    }
+
+    /// Sends `message` to session `id`, as a reply to `request_id`.
+    fn deliver(&self, id: Uuid, request_id: RequestId, message: ClientMessage) {
+        let addr = match self.get_address(&id) {
+            Some(addr) => addr,
             None => {
                 tracing::error!("No address found for id: {:?}", id);
+                return;
             }
+        };
+        let message = ClientMessage {
+            request_id: request_id.client,
+            dispatch: request_id.dispatch,
+            ..message
+        };
+        if let Err(e) = addr.do_send(message) {
+            tracing::error!("Failed to send message from {}: {:?}", Self::NAME, e);
         }
     }
 
-    fn task_from_message(&self, task_message: TaskMessage) -> Result<Self::Task, Self::Error>
+    fn task_from_message(&self, task_message: &TaskMessage) -> Result<Self::Task, Self::Error>
     where
-        Self::Task: serde::de::DeserializeOwned,
-        Self::Error: std::error::Error,
-        anyhow::Error: Into<Self::Error>,
+        Self::Task: DeserializeOwned,
+        Self::Error: ClientError + From<WebsocketError>,
     {
-        let task_name = format!("{:?}", task_message.name);
-        let task = serde_json::from_str::<Self::Task>(&task_name)
-            .context("Failed to deserialize task name.")
-            .map_err(|e| e.into());
+        let task = parse_task::<Self::Task>(task_message).map_err(Self::Error::from);
 
         if let Err(e) = &task {
-            self.send_error(task_message.payload.id, e);
+            self.send_error(
+                task_message.payload.id,
+                task_message.payload.request_id.clone(),
+                e,
+            );
         }
 
         task
     }
 }
+
+/// Decodes a `TaskMessage` into the task enum of a subsystem.
+///
+/// Task enums are expected to be adjacently tagged, using `task` for the
+/// name and `payload` for the data.
+pub fn parse_task<T: DeserializeOwned>(task_message: &TaskMessage) -> Result<T, WebsocketError> {
+    let names = task_names::<T>();
+    if !names.contains(&task_message.name.as_str()) {
+        return Err(WebsocketError::UnknownTask(
+            task_message.name.clone(),
+            names.iter().map(|name| name.to_string()).collect(),
+        ));
+    }
+
+    let value = serde_json::json!({
+        "task": task_message.name,
+        "payload": task_message.payload.data,
+    });
+    serde_json::from_value(value)
+        .map_err(|e| WebsocketError::InvalidPayload(task_message.name.clone(), e))
+}
+
+/// Names of the tasks of a (adjacently tagged) task enum.
+pub fn task_names<T: DeserializeOwned>() -> &'static [&'static str] {
+    /// Error that captures the variants reported by serde on `unknown_variant`.
+    #[derive(Debug)]
+    struct Variants(&'static [&'static str]);
+
+    impl std::fmt::Display for Variants {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "{:?}", self.0)
+        }
+    }
+
+    impl std::error::Error for Variants {}
+
+    impl de::Error for Variants {
+        fn custom<M: std::fmt::Display>(_msg: M) -> Self {
+            Self(&[])
+        }
+
+        fn unknown_variant(_variant: &str, expected: &'static [&'static str]) -> Self {
+            Self(expected)
+        }
+    }
+
+    // An empty task name is never valid, so serde reports every known variant
+    let deserializer = MapDeserializer::<_, Variants>::new(std::iter::once(("task", "")));
+    match T::deserialize(deserializer) {
+        Err(Variants(names)) => names,
+        Ok(_) => &[],
+    }
+}
@@ -0,0 +1,160 @@
+use crate::helpers::{next_result, send_message, spawn_app};
+use actix_websockets::{
+    authentication::{sign_token, TokenClaims},
+    configuration::get_configuration,
+    startup::Application,
+};
+use awc::{error::WsClientError, http::StatusCode, Client};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+fn assert_unauthorized(result: Result<impl Sized, WsClientError>) {
+    match result {
+        Err(WsClientError::InvalidResponseStatus(status)) => {
+            assert_eq!(StatusCode::UNAUTHORIZED, status)
+        }
+        Err(e) => panic!("Unexpected error: {:?}", e),
+        Ok(_) => panic!("Handshake should have been rejected."),
+    }
+}
+
+#[actix_rt::test]
+async fn handshake_without_credentials_is_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let result = Client::new()
+        .ws(format!("{}/ws/", app.address))
+        .connect()
+        .await;
+
+    // Assert
+    assert_unauthorized(result);
+}
+
+#[actix_rt::test]
+async fn handshake_with_unknown_token_is_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let result = Client::new()
+        .ws(format!("{}/ws/", app.address))
+        .bearer_auth("unknown-token")
+        .connect()
+        .await;
+
+    // Assert
+    assert_unauthorized(result);
+}
+
+#[actix_rt::test]
+async fn handshake_with_expired_signed_token_is_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    let claims = TokenClaims {
+        sub: "bob".into(),
+        exp: now() - 1,
+        roles: vec!["admin".into()],
+    };
+    let token = sign_token(&app.hmac_secret, &claims).unwrap();
+
+    // Act
+    let result = Client::new()
+        .ws(format!("{}/ws/", app.address))
+        .bearer_auth(&token)
+        .connect()
+        .await;
+
+    // Assert
+    assert_unauthorized(result);
+}
+
+#[actix_rt::test]
+async fn handshake_with_signed_token_is_accepted() {
+    // Arrange
+    let app = spawn_app().await;
+    let claims = TokenClaims {
+        sub: "bob".into(),
+        exp: now() + 60,
+        roles: vec!["admin".into()],
+    };
+    let token = sign_token(&app.hmac_secret, &claims).unwrap();
+
+    // Act
+    let (_response, mut connection) = Client::new()
+        .ws(format!("{}/ws/", app.address))
+        .bearer_auth(&token)
+        .connect()
+        .await
+        .expect("Failed to connect to websocket.");
+    send_message(
+        &mut connection,
+        r#"{"system":"meta","task":"list_systems"}"#,
+    )
+    .await;
+    let result = next_result(&mut connection).await;
+
+    // Assert
+    assert!(result.success);
+}
+
+#[actix_rt::test]
+async fn token_is_accepted_through_websocket_protocol() {
+    // Arrange
+    let app = spawn_app().await;
+    let protocol = format!("bearer.{}", app.token);
+
+    // Act
+    let (response, _connection) = Client::new()
+        .ws(format!("{}/ws/", app.address))
+        .protocols([protocol.as_str()])
+        .connect()
+        .await
+        .expect("Failed to connect to websocket.");
+
+    // Assert
+    let echoed = response
+        .headers()
+        .get("sec-websocket-protocol")
+        .expect("Protocol was not echoed back.");
+    assert_eq!(protocol, echoed.to_str().unwrap());
+}
+
+#[actix_rt::test]
+async fn token_is_accepted_through_query_parameter() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let result = Client::new()
+        .ws(format!("{}/ws/?token={}", app.address, app.token))
+        .connect()
+        .await;
+
+    // Assert
+    assert!(result.is_ok(), "Handshake was rejected: {:?}", result.err());
+}
+
+#[actix_rt::test]
+async fn app_fails_to_build_when_auth_has_no_credentials() {
+    // Arrange
+    let mut configuration = get_configuration().expect("Failed to read configuration.");
+    configuration.port = 0;
+    configuration.auth.enabled = true;
+    configuration.auth.tokens = vec![];
+    configuration.auth.hmac_secret = None;
+
+    // Act
+    let result = Application::build(configuration).await;
+
+    // Assert
+    assert!(result.is_err(), "App should not start without credentials.");
+}
@@ -0,0 +1,79 @@
+use crate::helpers::{next_result, send_message, spawn_app};
+use actix_websockets::websocket::error::ErrorPayload;
+
+#[actix_rt::test]
+async fn intern_can_run_allowed_tasks() {
+    // Arrange
+    let app = spawn_app().await;
+    let mut connection = app.connect_with_token(&app.intern_token).await;
+
+    // Act
+    send_message(
+        &mut connection,
+        r#"{"system":"pc_usage","task":"cpu_load"}"#,
+    )
+    .await;
+    let result = next_result(&mut connection).await;
+
+    // Assert
+    assert!(result.success, "{:?}", result.payload);
+}
+
+#[actix_rt::test]
+async fn intern_cannot_run_forbidden_tasks() {
+    // Arrange
+    let app = spawn_app().await;
+    let mut connection = app.connect_with_token(&app.intern_token).await;
+    let message =
+        r#"{"system":"python_repo","task":"get_files","payload":{"path":"."},"request_id":"r1"}"#;
+
+    // Act
+    send_message(&mut connection, message).await;
+    let result = next_result(&mut connection).await;
+
+    // Assert
+    assert!(!result.success);
+    assert_eq!(Some("r1".to_string()), result.request_id);
+    let payload = serde_json::from_value::<ErrorPayload>(result.payload).unwrap();
+    assert_eq!("forbidden", payload.code);
+    assert_eq!("python_repo", payload.details["system"]);
+    assert_eq!("get_files", payload.details["task"]);
+}
+
+#[actix_rt::test]
+async fn intern_cannot_subscribe_to_forbidden_tasks() {
+    // Arrange
+    let app = spawn_app().await;
+    let mut connection = app.connect_with_token(&app.intern_token).await;
+    let allowed = r#"{"system":"pc_usage","task":"subscribe","payload":{"task":"cpu_load","interval":200},"request_id":"r1"}"#;
+    let forbidden = r#"{"system":"pc_usage","task":"subscribe","payload":{"task":"processes","interval":1000},"request_id":"r2"}"#;
+
+    // Act
+    send_message(&mut connection, forbidden).await;
+    let denied = next_result(&mut connection).await;
+    send_message(&mut connection, allowed).await;
+    let ack = next_result(&mut connection).await;
+
+    // Assert
+    assert!(!denied.success);
+    assert_eq!(Some("r2".to_string()), denied.request_id);
+    let payload = serde_json::from_value::<ErrorPayload>(denied.payload).unwrap();
+    assert_eq!("forbidden", payload.code);
+    assert_eq!("pc_usage", payload.details["system"]);
+    assert_eq!("processes", payload.details["task"]);
+    assert!(ack.success, "{:?}", ack.payload);
+    assert_eq!(Some("r1".to_string()), ack.request_id);
+}
+
+#[actix_rt::test]
+async fn admin_can_run_every_task() {
+    // Arrange
+    let app = spawn_app().await;
+    let message = r#"{"system":"python_repo","task":"get_files","payload":{"path":"."}}"#;
+
+    // Act
+    let result = app.get_first_result(message).await;
+
+    // Assert
+    assert!(result.success, "{:?}", result.payload);
+}
@@ -11,6 +11,7 @@ async fn client_receives_heartbeat_every_x_milliseconds() {
 
     let (_response, mut connection) = Client::new()
         .ws(format!("{}/ws/", app.address))
+        .bearer_auth(&app.token)
         .connect()
         .await
         .expect("Failed to connect to websocket.");
@@ -44,6 +45,7 @@ async fn client_disconnects_after_x_milliseconds() {
 
     let (_response, mut connection) = Client::new()
         .ws(format!("{}/ws/", app.address))
+        .bearer_auth(&app.token)
         .connect()
         .await
         .expect("Failed to connect to websocket.");
@@ -57,7 +59,7 @@ async fn client_disconnects_after_x_milliseconds() {
         tokio::select! {
             msg = connection.next() => {
                 tracing::info!("==> {:?}", msg);
-                if let None = msg {
+                if msg.is_none() {
                     disconnected = true;
                     break;
                 }
@@ -80,6 +82,7 @@ async fn client_stays_alive_if_responds_pings() {
 
     let (_response, mut connection) = Client::new()
         .ws(format!("{}/ws/", app.address))
+        .bearer_auth(&app.token)
         .connect()
         .await
         .expect("Failed to connect to websocket.");
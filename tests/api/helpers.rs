@@ -1,14 +1,20 @@
 use actix_web_actors::ws;
 use actix_websockets::{
-    configuration::get_configuration,
+    configuration::{get_configuration, Settings, StaticToken},
     startup::Application,
     telemetry::{get_subscriber, init_subscriber},
-    websocket::message::ClientMessage,
+    websocket::{
+        message::ClientMessage,
+        metrics_source::{MetricsSource, ScriptedMetrics, SystemMetrics},
+        pc_usage::{CpuLoadResult, LoadAverage, MemoryResult, MemoryUsage, SwapUsage},
+        registry::SubSystemRegistry,
+    },
 };
 use awc::Client;
-use futures::{SinkExt, StreamExt};
+use futures::{Sink, SinkExt, Stream, StreamExt};
 use once_cell::sync::Lazy;
-use std::time::Duration;
+use std::{sync::Arc, time::Duration};
+use uuid::Uuid;
 
 // Ensure that 'tracing' stack is only initialized once using `once_cell`
 static TRACING: Lazy<()> = Lazy::new(|| {
@@ -26,41 +32,124 @@ static TRACING: Lazy<()> = Lazy::new(|| {
 
 pub struct TestApp {
     pub address: String,
+    #[allow(dead_code)]
     pub port: u16,
+    pub registry: SubSystemRegistry,
+    /// Token with the `admin` role, allowed to run every task.
+    pub token: String,
+    /// Token with the `intern` role, only allowed to run `pc_usage.cpu_load`,
+    /// `pc_usage.subscribe` and `meta.*`.
+    #[allow(dead_code)]
+    pub intern_token: String,
+    #[allow(dead_code)]
+    pub hmac_secret: String,
 }
 
 impl TestApp {
-    pub async fn get_first_result(&self, message: &str) -> ClientMessage {
-        let (_response, mut connection) = Client::new()
+    pub async fn connect(&self) -> impl WebsocketConnection {
+        self.connect_with_token(&self.token).await
+    }
+
+    pub async fn connect_with_token(&self, token: &str) -> impl WebsocketConnection {
+        let (_response, connection) = Client::new()
             .ws(format!("{}/ws/", self.address))
+            .bearer_auth(token)
             .connect()
             .await
             .expect("Failed to connect to websocket.");
-
         connection
-            .send(awc::ws::Message::Text(message.into()))
+    }
+
+    /// Body of the `/metrics` endpoint.
+    #[allow(dead_code)]
+    pub async fn get_metrics(&self) -> String {
+        let mut response = Client::new()
+            .get(format!("{}/metrics", self.address))
+            .send()
             .await
-            .expect("Failed to send message.");
-
-        loop {
-            match connection.next().await {
-                Some(Ok(ws::Frame::Text(msg))) => {
-                    let msg = serde_json::from_slice::<ClientMessage>(&msg)
-                        .expect(&format!("Failed to parse JSON: {:?}", msg));
-                    tracing::info!("RESULT: {:?}", msg);
-                    return msg;
-                }
-                Some(Ok(ws::Frame::Ping(_))) => {}
-                err => {
-                    tracing::error!("Receive message: {:?}", err);
-                    panic!("Failed to receive message.");
-                }
+            .expect("Failed to execute request.");
+        assert!(response.status().is_success());
+        let body = response.body().await.expect("Failed to read body.");
+        String::from_utf8(body.to_vec()).expect("Metrics are not valid UTF-8.")
+    }
+
+    pub async fn get_first_result(&self, message: &str) -> ClientMessage {
+        let mut connection = self.connect().await;
+        send_message(&mut connection, message).await;
+        next_result(&mut connection).await
+    }
+}
+
+pub trait WebsocketConnection:
+    Stream<Item = Result<ws::Frame, ws::ProtocolError>>
+    + Sink<awc::ws::Message, Error = ws::ProtocolError>
+    + Unpin
+{
+}
+
+impl<T> WebsocketConnection for T where
+    T: Stream<Item = Result<ws::Frame, ws::ProtocolError>>
+        + Sink<awc::ws::Message, Error = ws::ProtocolError>
+        + Unpin
+{
+}
+
+pub async fn send_message(connection: &mut impl WebsocketConnection, message: &str) {
+    connection
+        .send(awc::ws::Message::Text(message.into()))
+        .await
+        .expect("Failed to send message.");
+}
+
+/// Waits for the next `ClientMessage`, answering pings meanwhile.
+pub async fn next_result(connection: &mut impl WebsocketConnection) -> ClientMessage {
+    loop {
+        match connection.next().await {
+            Some(Ok(ws::Frame::Text(msg))) => {
+                let msg = serde_json::from_slice::<ClientMessage>(&msg)
+                    .unwrap_or_else(|_| panic!("Failed to parse JSON: {:?}", msg));
+                tracing::info!("RESULT: {:?}", msg);
+                return msg;
+            }
+            Some(Ok(ws::Frame::Ping(msg))) => {
+                connection
+                    .send(awc::ws::Message::Pong(msg))
+                    .await
+                    .expect("Failed to send Pong message.");
+            }
+            err => {
+                tracing::error!("Receive message: {:?}", err);
+                panic!("Failed to receive message.");
             }
         }
     }
 }
 
+/// Same as `next_result` but gives up after `timeout`.
+pub async fn next_result_within(
+    connection: &mut impl WebsocketConnection,
+    timeout: Duration,
+) -> Option<ClientMessage> {
+    tokio::time::timeout(timeout, next_result(connection))
+        .await
+        .ok()
+}
+
 pub async fn spawn_app() -> TestApp {
+    spawn_app_with_metrics(Arc::new(SystemMetrics)).await
+}
+
+/// Same as `spawn_app`, reading the host metrics from `metrics`.
+pub async fn spawn_app_with_metrics(metrics: Arc<dyn MetricsSource>) -> TestApp {
+    spawn_app_with(metrics, |_| {}).await
+}
+
+/// Same as `spawn_app_with_metrics`, letting `configure` change the settings
+/// before the app starts.
+pub async fn spawn_app_with(
+    metrics: Arc<dyn MetricsSource>,
+    configure: impl FnOnce(&mut Settings),
+) -> TestApp {
     // Set up tracing
     Lazy::force(&TRACING);
 
@@ -71,20 +160,100 @@ pub async fn spawn_app() -> TestApp {
         c.port = 0;
         c.websocket.heartbeat_interval = Duration::from_millis(50);
         c.websocket.client_timeout = Duration::from_millis(250);
+        c.pc_usage.history_size = 100;
+        c.pc_usage.sample_interval = Duration::from_millis(50);
+        c.auth.enabled = true;
+        c.auth.tokens = vec![
+            StaticToken {
+                token: Uuid::new_v4().to_string(),
+                identity: "test".into(),
+                roles: vec!["admin".into()],
+            },
+            StaticToken {
+                token: Uuid::new_v4().to_string(),
+                identity: "intern".into(),
+                roles: vec!["intern".into()],
+            },
+        ];
+        c.auth.hmac_secret = Some(Uuid::new_v4().to_string());
+        c.policy.enabled = true;
+        c.policy.roles = vec![
+            ("admin".to_string(), vec!["*".to_string()]),
+            (
+                "intern".to_string(),
+                vec![
+                    "pc_usage.cpu_load".to_string(),
+                    "pc_usage.subscribe".to_string(),
+                    "meta.*".to_string(),
+                ],
+            ),
+        ]
+        .into_iter()
+        .collect();
+        configure(&mut c);
         c
     };
+    let token = configuration.auth.tokens[0].token.clone();
+    let intern_token = configuration.auth.tokens[1].token.clone();
+    let hmac_secret = configuration.auth.hmac_secret.clone().unwrap();
 
     // Launch app as background task
-    let application = Application::build(configuration)
+    let application = Application::build_with_metrics(configuration, metrics)
         .await
         .expect("Failed to build application.");
     let application_port = application.port();
-    let _ = tokio::spawn(application.run_until_stopped());
+    let registry = application.registry().clone();
+    drop(tokio::spawn(application.run_until_stopped()));
 
     let test_app = TestApp {
         address: format!("http://127.0.0.1:{}", application_port),
         port: application_port,
+        registry,
+        token,
+        intern_token,
+        hmac_secret,
     };
 
     test_app
 }
+
+pub const GIB: u64 = 1024 * 1024 * 1024;
+
+/// Load of a core, without nice, interrupt and iowait time.
+pub fn core(user: f32, system: f32, idle: f32) -> CpuLoadResult {
+    CpuLoadResult {
+        user,
+        system,
+        nice: 0.0,
+        interrupt: 0.0,
+        idle,
+        iowait: Some(0.0),
+        frequency: None,
+    }
+}
+
+/// Two cores averaging 0.375 user and 0.625 busy, with 4 GiB of 8 GiB used,
+/// a 1.5 load average over the last minute and one hour of uptime.
+pub fn scripted_metrics() -> ScriptedMetrics {
+    ScriptedMetrics::default()
+        .cpu_loads(vec![vec![core(0.5, 0.25, 0.25), core(0.25, 0.25, 0.5)]])
+        .memory(vec![MemoryResult {
+            memory: MemoryUsage {
+                total: 8 * GIB,
+                free: GIB,
+                available: Some(4 * GIB),
+                cached: Some(2 * GIB),
+            },
+            swap: SwapUsage {
+                total: 2 * GIB,
+                free: GIB,
+                used: GIB,
+            },
+        }])
+        .load_averages(vec![LoadAverage {
+            one: 1.5,
+            five: 1.0,
+            fifteen: 0.5,
+        }])
+        .uptimes(vec![Duration::from_secs(3600)])
+}
@@ -1,4 +1,10 @@
+mod authentication;
+mod authorization;
 mod heartbeat;
 mod helpers;
+mod message;
+mod meta;
+mod metrics;
 mod pc_usage;
 mod python_repo;
+mod sessions;
@@ -0,0 +1,163 @@
+use crate::helpers::spawn_app;
+use actix_websockets::websocket::error::ErrorPayload;
+
+#[actix_rt::test]
+async fn request_id_is_echoed_on_success() {
+    // Arrange
+    let app = spawn_app().await;
+    let message = serde_json::json!({
+        "system": "python_repo",
+        "task": "get_files",
+        "payload": "tests/examples",
+        "request_id": "request-1"
+    })
+    .to_string();
+
+    // Act
+    let result = app.get_first_result(&message).await;
+
+    // Assert
+    assert_eq!(result.system.unwrap(), "python_repo");
+    assert!(result.success, "Call was not successful.");
+    assert_eq!(result.request_id.as_deref(), Some("request-1"));
+}
+
+#[actix_rt::test]
+async fn request_id_is_echoed_on_task_error() {
+    // Arrange
+    let app = spawn_app().await;
+    let message = serde_json::json!({
+        "system": "pc_usage",
+        "task": "invalid_task_name",
+        "request_id": "request-2"
+    })
+    .to_string();
+
+    // Act
+    let result = app.get_first_result(&message).await;
+
+    // Assert
+    assert!(!result.success, "Call should not success.");
+    assert_eq!(result.request_id.as_deref(), Some("request-2"));
+}
+
+#[actix_rt::test]
+async fn request_id_is_echoed_on_parse_error() {
+    // Arrange
+    let app = spawn_app().await;
+    let message = serde_json::json!({
+        "system": "invalid_system",
+        "task": "some_task",
+        "request_id": "request-3"
+    })
+    .to_string();
+
+    // Act
+    let result = app.get_first_result(&message).await;
+
+    // Assert
+    assert_eq!(result.system, None);
+    assert!(!result.success, "Call should not success.");
+    assert_eq!(result.request_id.as_deref(), Some("request-3"));
+}
+
+#[actix_rt::test]
+async fn request_id_is_omitted_when_not_sent() {
+    // Arrange
+    let app = spawn_app().await;
+    let message = serde_json::json!({
+        "system": "python_repo",
+        "task": "get_files",
+        "payload": "tests/examples"
+    })
+    .to_string();
+
+    // Act
+    let result = app.get_first_result(&message).await;
+
+    // Assert
+    assert_eq!(result.request_id, None);
+}
+
+#[actix_rt::test]
+async fn receive_error_listing_systems_on_unknown_system() {
+    // Arrange
+    let app = spawn_app().await;
+    let message = serde_json::json!({
+        "system": "invalid_system",
+        "task": "some_task",
+    })
+    .to_string();
+
+    // Act
+    let result = app.get_first_result(&message).await;
+
+    // Assert
+    assert_eq!(result.system, None);
+    assert!(!result.success, "Call should not success.");
+    let payload = result.payload.to_string();
+    for system in app.registry.names() {
+        assert!(
+            payload.contains(system),
+            "{:?} not listed in error.",
+            system
+        );
+    }
+}
+
+#[actix_rt::test]
+async fn errors_carry_machine_readable_codes() {
+    // Arrange
+    let app = spawn_app().await;
+    let cases = [
+        ("{", "parse_error"),
+        (
+            r#"{"system": "invalid_system", "task": "x"}"#,
+            "unknown_system",
+        ),
+        (
+            r#"{"system": "pc_usage", "task": "invalid_task"}"#,
+            "unknown_task",
+        ),
+        (
+            r#"{"system": "python_repo", "task": "get_files", "payload": {}}"#,
+            "invalid_payload",
+        ),
+        (
+            r#"{"system": "python_repo", "task": "get_files", "payload": {"path": "tests/some_incorrect_path"}}"#,
+            "invalid_path",
+        ),
+    ];
+
+    for (message, code) in cases {
+        // Act
+        let result = app.get_first_result(message).await;
+
+        // Assert
+        assert!(!result.success, "Call should not success: {}", message);
+        let payload = serde_json::from_value::<ErrorPayload>(result.payload)
+            .expect("Failed to deserialize error payload.");
+        assert_eq!(payload.code, code, "Unexpected code for: {}", message);
+        assert!(!payload.message.is_empty(), "Empty error message.");
+    }
+}
+
+#[actix_rt::test]
+async fn invalid_path_error_includes_path_in_details() {
+    // Arrange
+    let app = spawn_app().await;
+    let message = serde_json::json!({
+        "system": "python_repo",
+        "task": "get_files",
+        "payload": { "path": "tests/some_incorrect_path" }
+    })
+    .to_string();
+
+    // Act
+    let result = app.get_first_result(&message).await;
+
+    // Assert
+    let payload = serde_json::from_value::<ErrorPayload>(result.payload)
+        .expect("Failed to deserialize error payload.");
+    assert_eq!(payload.details["path"], "tests/some_incorrect_path");
+}
@@ -0,0 +1,98 @@
+use crate::helpers::spawn_app;
+use actix_websockets::websocket::{
+    error::ErrorPayload,
+    meta::{TaskDescription, TaskSummary},
+};
+
+#[actix_rt::test]
+async fn list_systems_returns_registered_systems() {
+    // Arrange
+    let app = spawn_app().await;
+    let message = serde_json::json!({
+        "system": "meta",
+        "task": "list_systems",
+    })
+    .to_string();
+
+    // Act
+    let result = app.get_first_result(&message).await;
+
+    // Assert
+    assert_eq!(result.system.unwrap(), "meta");
+    assert!(result.success, "Call was not successful.");
+    let payload = serde_json::from_value::<Vec<String>>(result.payload)
+        .expect("Failed to deserialize result.");
+    assert_eq!(payload, app.registry.names());
+}
+
+#[actix_rt::test]
+async fn list_tasks_returns_system_tasks() {
+    // Arrange
+    let app = spawn_app().await;
+    let message = serde_json::json!({
+        "system": "meta",
+        "task": "list_tasks",
+        "payload": { "system": "pc_usage" }
+    })
+    .to_string();
+
+    // Act
+    let result = app.get_first_result(&message).await;
+
+    // Assert
+    assert!(result.success, "Call was not successful.");
+    let payload = serde_json::from_value::<Vec<TaskSummary>>(result.payload)
+        .expect("Failed to deserialize result.");
+    let names = payload
+        .into_iter()
+        .map(|task| task.name)
+        .collect::<Vec<_>>();
+    assert!(names.contains(&"cpu_load".to_string()), "{:?}", names);
+}
+
+#[actix_rt::test]
+async fn describe_task_returns_schemas() {
+    // Arrange
+    let app = spawn_app().await;
+    let message = serde_json::json!({
+        "system": "meta",
+        "task": "describe_task",
+        "payload": { "system": "python_repo", "task": "get_files" }
+    })
+    .to_string();
+
+    // Act
+    let result = app.get_first_result(&message).await;
+
+    // Assert
+    assert!(result.success, "Call was not successful.");
+    let payload = serde_json::from_value::<TaskDescription>(result.payload)
+        .expect("Failed to deserialize result.");
+    assert_eq!(payload.name, "get_files");
+    assert_eq!(
+        payload.payload["anyOf"][1]["required"],
+        serde_json::json!(["path"])
+    );
+    assert_eq!(payload.result["type"], "array");
+}
+
+#[actix_rt::test]
+async fn describe_task_receives_error_on_unknown_task() {
+    // Arrange
+    let app = spawn_app().await;
+    let message = serde_json::json!({
+        "system": "meta",
+        "task": "describe_task",
+        "payload": { "system": "pc_usage", "task": "invalid_task_name" }
+    })
+    .to_string();
+
+    // Act
+    let result = app.get_first_result(&message).await;
+
+    // Assert
+    assert!(!result.success, "Call should not success.");
+    let payload = serde_json::from_value::<ErrorPayload>(result.payload)
+        .expect("Failed to deserialize error payload.");
+    assert_eq!(payload.code, "unknown_task");
+}
@@ -0,0 +1,111 @@
+use crate::helpers::{
+    next_result, scripted_metrics, send_message, spawn_app, spawn_app_with_metrics,
+};
+use std::sync::Arc;
+
+#[actix_rt::test]
+async fn metrics_reports_host_metrics() {
+    // Arrange
+    let app = spawn_app_with_metrics(Arc::new(scripted_metrics())).await;
+
+    // Act
+    let metrics = app.get_metrics().await;
+
+    // Assert
+    assert!(
+        metrics.contains("host_memory_total_bytes 8589934592"),
+        "{}",
+        metrics
+    );
+    assert!(
+        metrics.contains("host_memory_used_bytes 4294967296"),
+        "{}",
+        metrics
+    );
+    assert!(
+        metrics.contains("host_load_average{period=\"1m\"} 1.5"),
+        "{}",
+        metrics
+    );
+    assert!(metrics.contains("host_uptime_seconds 3600"), "{}", metrics);
+}
+
+#[actix_rt::test]
+async fn metrics_counts_sessions_tasks_and_errors() {
+    // Arrange
+    let app = spawn_app().await;
+    let mut connection = app.connect().await;
+    let messages = [
+        serde_json::json!({ "system": "pc_usage", "task": "memory" }),
+        serde_json::json!({ "system": "pc_usage", "task": "invalid_task_name" }),
+        serde_json::json!({ "system": "invalid_system", "task": "memory" }),
+    ];
+    for message in messages {
+        send_message(&mut connection, &message.to_string()).await;
+        next_result(&mut connection).await;
+    }
+
+    // Act
+    let metrics = app.get_metrics().await;
+
+    // Assert
+    let expected = [
+        "websocket_sessions 1",
+        "websocket_tasks_total{system=\"pc_usage\",task=\"memory\"} 1",
+        "websocket_tasks_total{system=\"pc_usage\",task=\"unknown\"} 1",
+        "websocket_task_errors_total{system=\"pc_usage\",task=\"unknown\"} 1",
+        "websocket_task_errors_total{system=\"unknown\",task=\"unknown\"} 1",
+        "websocket_task_duration_seconds_count{system=\"pc_usage\",task=\"memory\"} 1",
+        "websocket_messages_sent_total{system=\"pc_usage\"} 2",
+        "websocket_messages_sent_total{system=\"websocket\"} 1",
+    ];
+    for line in expected {
+        assert!(
+            metrics.contains(line),
+            "Missing {:?} in:\n{}",
+            line,
+            metrics
+        );
+    }
+    assert!(!metrics.contains("invalid_system"), "{}", metrics);
+    assert!(!metrics.contains("invalid_task_name"), "{}", metrics);
+}
+
+#[actix_rt::test]
+async fn metrics_match_replies_to_their_task() {
+    // Arrange
+    let app = spawn_app().await;
+    let mut connection = app.connect().await;
+    // Neither task has a `request_id`, and the slow one is sent first
+    let network = serde_json::json!({
+        "system": "pc_usage",
+        "task": "network",
+        "payload": { "rate": true },
+    });
+    let mounts = serde_json::json!({
+        "system": "pc_usage",
+        "task": "mounts",
+        "payload": { "path": "/this/path/does/not/exist" },
+    });
+    send_message(&mut connection, &network.to_string()).await;
+    send_message(&mut connection, &mounts.to_string()).await;
+    let first = next_result(&mut connection).await;
+    let second = next_result(&mut connection).await;
+
+    // Act
+    let metrics = app.get_metrics().await;
+
+    // Assert
+    assert!(!first.success, "Mounts should reply first.");
+    assert!(second.success, "Network should succeed.");
+    assert!(
+        metrics.contains("websocket_task_errors_total{system=\"pc_usage\",task=\"mounts\"} 1"),
+        "{}",
+        metrics
+    );
+    assert!(
+        !metrics.contains("websocket_task_errors_total{system=\"pc_usage\",task=\"network\"}"),
+        "{}",
+        metrics
+    );
+}
@@ -1,5 +1,25 @@
-use crate::helpers::spawn_app;
-use actix_websockets::websocket::{message::WebsocketSystems, pc_usage::CpuLoadResult};
+use crate::helpers::{
+    core, next_result, next_result_within, scripted_metrics, send_message, spawn_app,
+    spawn_app_with, spawn_app_with_metrics, GIB,
+};
+use actix_websockets::{
+    cgroup::{CgroupUsage, CgroupVersion},
+    procfs::PressureStall,
+    websocket::{
+        alerts::{AlertEvent, AlertInfo, AlertState},
+        error::ErrorPayload,
+        history::HistorySample,
+        metrics_source::SystemMetrics,
+        pc_usage::{
+            CpuLoadResult, CpuResult, DiskIoResult, HostInfoResult, MemoryResult, MountResult,
+            NetworkResult, PressureResult, ProcessResult, ProcessUsageResult,
+        },
+    },
+};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 #[actix_rt::test]
 async fn cpu_load_receives_results() {
@@ -16,11 +36,1055 @@ async fn cpu_load_receives_results() {
     let result = app.get_first_result(&message).await;
 
     // Assert
-    assert_eq!(result.system.unwrap(), WebsocketSystems::PcUsage);
+    assert_eq!(result.system.unwrap(), "pc_usage");
     assert!(result.success, "Call was not successful.");
     let payload = serde_json::from_value::<Vec<CpuLoadResult>>(result.payload)
         .expect("Failed to deserialize result.");
-    assert!(payload.len() > 0, "Empty results.");
+    assert!(!payload.is_empty(), "Empty results.");
+}
+
+#[actix_rt::test]
+async fn cpu_load_keeps_previous_fields() {
+    // Arrange
+    let app = spawn_app().await;
+    let message = serde_json::json!({
+        "system": "pc_usage",
+        "task":  "cpu_load",
+    })
+    .to_string();
+
+    // Act
+    let result = app.get_first_result(&message).await;
+
+    // Assert
+    assert!(result.success, "Call was not successful.");
+    let cores = result.payload.as_array().expect("Result is not a list.");
+    assert!(!cores.is_empty(), "Empty results.");
+    for core in cores {
+        assert!(core["user"].is_number());
+        assert!(core["system"].is_number());
+    }
+}
+
+#[actix_rt::test]
+async fn cpu_receives_breakdown_and_aggregate() {
+    // Arrange
+    let app = spawn_app().await;
+    let message = serde_json::json!({
+        "system": "pc_usage",
+        "task":  "cpu",
+        "payload": { "frequency": true },
+    })
+    .to_string();
+
+    // Act
+    let result = app.get_first_result(&message).await;
+
+    // Assert
+    assert!(
+        result.success,
+        "Call was not successful: {:?}",
+        result.payload
+    );
+    let payload =
+        serde_json::from_value::<CpuResult>(result.payload).expect("Failed to deserialize result.");
+    assert!(!payload.cores.is_empty(), "Empty results.");
+    let total = payload.aggregate.user
+        + payload.aggregate.system
+        + payload.aggregate.nice
+        + payload.aggregate.interrupt
+        + payload.aggregate.idle
+        + payload.aggregate.iowait.unwrap_or(0.0);
+    assert!(total <= 1.01, "Aggregate is not a fraction: {}", total);
+}
+
+#[actix_rt::test]
+async fn cpu_receives_scripted_load() {
+    // Arrange
+    let app = spawn_app_with_metrics(Arc::new(scripted_metrics())).await;
+    let message = serde_json::json!({
+        "system": "pc_usage",
+        "task":  "cpu",
+    })
+    .to_string();
+
+    // Act
+    let result = app.get_first_result(&message).await;
+
+    // Assert
+    assert!(result.success, "Call was not successful.");
+    let payload =
+        serde_json::from_value::<CpuResult>(result.payload).expect("Failed to deserialize result.");
+    assert_eq!(2, payload.cores.len());
+    assert_eq!(0.5, payload.cores[0].user);
+    assert_eq!(0.375, payload.aggregate.user);
+    assert_eq!(0.25, payload.aggregate.system);
+    assert_eq!(0.375, payload.aggregate.idle);
+}
+
+#[actix_rt::test]
+async fn cpu_receives_fixture_frequencies() {
+    // Arrange
+    let app = spawn_app_with(Arc::new(scripted_metrics()), |c| {
+        c.pc_usage.sysfs_root = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/sys").into();
+    })
+    .await;
+    let message = serde_json::json!({
+        "system": "pc_usage",
+        "task":  "cpu",
+        "payload": { "frequency": true },
+    })
+    .to_string();
+
+    // Act
+    let result = app.get_first_result(&message).await;
+
+    // Assert
+    assert!(result.success, "Call was not successful.");
+    let payload =
+        serde_json::from_value::<CpuResult>(result.payload).expect("Failed to deserialize result.");
+    assert_eq!(Some(2400), payload.cores[0].frequency);
+    assert_eq!(Some(1800), payload.cores[1].frequency);
+}
+
+#[actix_rt::test]
+async fn memory_receives_scripted_usage() {
+    // Arrange
+    let app = spawn_app_with_metrics(Arc::new(scripted_metrics())).await;
+    let message = serde_json::json!({
+        "system": "pc_usage",
+        "task":  "memory",
+    })
+    .to_string();
+
+    // Act
+    let result = app.get_first_result(&message).await;
+
+    // Assert
+    assert!(result.success, "Call was not successful.");
+    let payload = serde_json::from_value::<MemoryResult>(result.payload)
+        .expect("Failed to deserialize result.");
+    assert_eq!(8 * GIB, payload.memory.total);
+    assert_eq!(4 * GIB, payload.memory.used());
+    assert_eq!(GIB, payload.swap.used);
+}
+
+#[actix_rt::test]
+async fn memory_receives_results() {
+    // Arrange
+    let app = spawn_app().await;
+    let message = serde_json::json!({
+        "system": "pc_usage",
+        "task":  "memory",
+    })
+    .to_string();
+
+    // Act
+    let result = app.get_first_result(&message).await;
+
+    // Assert
+    assert_eq!(result.system.unwrap(), "pc_usage");
+    assert!(result.success, "Call was not successful.");
+    let payload = serde_json::from_value::<MemoryResult>(result.payload)
+        .expect("Failed to deserialize result.");
+    assert!(payload.memory.total > 0, "Empty memory total.");
+    assert!(payload.memory.free <= payload.memory.total);
+    assert!(payload.swap.free <= payload.swap.total);
+}
+
+#[actix_rt::test]
+async fn subscribe_receives_periodic_memory() {
+    // Arrange
+    let app = spawn_app().await;
+    let mut connection = app.connect().await;
+    let message = serde_json::json!({
+        "system": "pc_usage",
+        "task": "subscribe",
+        "payload": { "task": "memory", "interval": 200 },
+        "request_id": "subscription"
+    })
+    .to_string();
+
+    // Act
+    send_message(&mut connection, &message).await;
+    let ack = next_result(&mut connection).await;
+    let first = next_result(&mut connection).await;
+
+    // Assert
+    assert!(ack.success, "Subscription was not successful.");
+    assert!(first.success, "Call was not successful.");
+    assert_eq!(first.request_id.as_deref(), Some("subscription"));
+    serde_json::from_value::<MemoryResult>(first.payload).expect("Failed to deserialize result.");
+}
+
+#[actix_rt::test]
+async fn mounts_receives_results() {
+    // Arrange
+    let app = spawn_app().await;
+    let message = serde_json::json!({
+        "system": "pc_usage",
+        "task":  "mounts",
+    })
+    .to_string();
+
+    // Act
+    let result = app.get_first_result(&message).await;
+
+    // Assert
+    assert!(result.success, "Call was not successful.");
+    let payload = serde_json::from_value::<Vec<MountResult>>(result.payload)
+        .expect("Failed to deserialize result.");
+    assert!(
+        payload.iter().any(|mount| mount.mounted_on == "/"),
+        "Root mount point not found."
+    );
+}
+
+#[actix_rt::test]
+async fn mounts_filters_by_path() {
+    // Arrange
+    let app = spawn_app().await;
+    let message = serde_json::json!({
+        "system": "pc_usage",
+        "task":  "mounts",
+        "payload": { "path": "/" },
+    })
+    .to_string();
+
+    // Act
+    let result = app.get_first_result(&message).await;
+
+    // Assert
+    assert!(result.success, "Call was not successful.");
+    let payload = serde_json::from_value::<Vec<MountResult>>(result.payload)
+        .expect("Failed to deserialize result.");
+    assert_eq!(1, payload.len());
+    assert_eq!("/", payload[0].mounted_on);
+    assert!(payload[0].available <= payload[0].total);
+}
+
+#[actix_rt::test]
+async fn mounts_receives_error_on_unknown_path() {
+    // Arrange
+    let app = spawn_app().await;
+    let message = serde_json::json!({
+        "system": "pc_usage",
+        "task":  "mounts",
+        "payload": { "path": "/some/unknown/mount" },
+    })
+    .to_string();
+
+    // Act
+    let result = app.get_first_result(&message).await;
+
+    // Assert
+    assert!(!result.success, "Call should not success.");
+    let payload = serde_json::from_value::<ErrorPayload>(result.payload)
+        .expect("Failed to deserialize error.");
+    assert_eq!("invalid_path", payload.code);
+    assert_eq!("/some/unknown/mount", payload.details["path"]);
+}
+
+#[actix_rt::test]
+async fn network_receives_results() {
+    // Arrange
+    let app = spawn_app().await;
+    let message = serde_json::json!({
+        "system": "pc_usage",
+        "task":  "network",
+    })
+    .to_string();
+
+    // Act
+    let result = app.get_first_result(&message).await;
+
+    // Assert
+    assert!(result.success, "Call was not successful.");
+    let payload = serde_json::from_value::<Vec<NetworkResult>>(result.payload)
+        .expect("Failed to deserialize result.");
+    assert!(!payload.is_empty(), "Empty results.");
+    assert!(payload.iter().all(|o| o.rates.is_none()));
+}
+
+#[actix_rt::test]
+async fn network_rate_receives_results() {
+    // Arrange
+    let app = spawn_app().await;
+    let message = serde_json::json!({
+        "system": "pc_usage",
+        "task":  "network",
+        "payload": { "rate": true },
+    })
+    .to_string();
+
+    // Act
+    let result = app.get_first_result(&message).await;
+
+    // Assert
+    assert!(result.success, "Call was not successful.");
+    let payload = serde_json::from_value::<Vec<NetworkResult>>(result.payload)
+        .expect("Failed to deserialize result.");
+    assert!(!payload.is_empty(), "Empty results.");
+    for rates in payload.iter().filter_map(|o| o.rates.as_ref()) {
+        assert!(rates.rx_bytes_per_second >= 0.0);
+        assert!(rates.tx_bytes_per_second >= 0.0);
+    }
+    assert!(payload.iter().any(|o| o.rates.is_some()), "Missing rates.");
+}
+
+#[actix_rt::test]
+async fn network_rate_does_not_block_other_tasks() {
+    // Arrange
+    let app = spawn_app().await;
+    let mut connection = app.connect().await;
+    let network = serde_json::json!({
+        "system": "pc_usage",
+        "task": "network",
+        "payload": { "rate": true },
+        "request_id": "network",
+    })
+    .to_string();
+    let memory = serde_json::json!({
+        "system": "pc_usage",
+        "task": "memory",
+        "request_id": "memory",
+    })
+    .to_string();
+
+    // Act
+    send_message(&mut connection, &network).await;
+    send_message(&mut connection, &memory).await;
+    let first = next_result(&mut connection).await;
+    let second = next_result(&mut connection).await;
+
+    // Assert
+    assert_eq!(first.request_id.as_deref(), Some("memory"));
+    assert_eq!(second.request_id.as_deref(), Some("network"));
+}
+
+#[actix_rt::test]
+async fn disk_io_receives_results() {
+    // Arrange
+    let app = spawn_app().await;
+    let message = serde_json::json!({
+        "system": "pc_usage",
+        "task":  "disk_io",
+    })
+    .to_string();
+
+    // Act
+    let result = app.get_first_result(&message).await;
+
+    // Assert
+    assert!(
+        result.success,
+        "Call was not successful: {:?}",
+        result.payload
+    );
+    let payload = serde_json::from_value::<Vec<DiskIoResult>>(result.payload)
+        .expect("Failed to deserialize result.");
+    for device in payload {
+        assert!(device.read_bytes_per_second >= 0.0);
+        assert!(device.write_bytes_per_second >= 0.0);
+        assert!((0.0..=1.0).contains(&device.utilization));
+    }
+}
+
+#[actix_rt::test]
+async fn disk_io_receives_fixture_devices() {
+    // Arrange
+    let app = spawn_app_with(Arc::new(SystemMetrics), |c| {
+        c.pc_usage.procfs_root = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/proc").into();
+    })
+    .await;
+    let message = serde_json::json!({
+        "system": "pc_usage",
+        "task":  "disk_io",
+    })
+    .to_string();
+
+    // Act
+    let result = app.get_first_result(&message).await;
+
+    // Assert
+    assert!(
+        result.success,
+        "Call was not successful: {:?}",
+        result.payload
+    );
+    let payload = serde_json::from_value::<Vec<DiskIoResult>>(result.payload)
+        .expect("Failed to deserialize result.");
+    let names = payload.iter().map(|o| o.name.as_str()).collect::<Vec<_>>();
+    // Idle devices are skipped and counters did not change
+    assert_eq!(vec!["nvme0n1", "nvme0n1p1"], names);
+    for device in payload {
+        assert_eq!(0.0, device.read_bytes_per_second);
+        assert_eq!(0.0, device.writes_per_second);
+        assert_eq!(0.0, device.utilization);
+    }
+}
+
+#[actix_rt::test]
+async fn disk_io_does_not_block_other_tasks() {
+    // Arrange
+    let app = spawn_app().await;
+    let mut connection = app.connect().await;
+    let disk_io = serde_json::json!({
+        "system": "pc_usage",
+        "task": "disk_io",
+        "request_id": "disk_io",
+    })
+    .to_string();
+    let memory = serde_json::json!({
+        "system": "pc_usage",
+        "task": "memory",
+        "request_id": "memory",
+    })
+    .to_string();
+
+    // Act
+    send_message(&mut connection, &disk_io).await;
+    send_message(&mut connection, &memory).await;
+    let first = next_result(&mut connection).await;
+    let second = next_result(&mut connection).await;
+
+    // Assert
+    assert_eq!(first.request_id.as_deref(), Some("memory"));
+    assert_eq!(second.request_id.as_deref(), Some("disk_io"));
+    assert!(second.success, "Call was not successful.");
+}
+
+#[actix_rt::test]
+async fn processes_receives_top_n_by_memory() {
+    // Arrange
+    let app = spawn_app().await;
+    let message = serde_json::json!({
+        "system": "pc_usage",
+        "task":  "processes",
+        "payload": { "sort_by": "memory", "limit": 3 },
+    })
+    .to_string();
+
+    // Act
+    let result = app.get_first_result(&message).await;
+
+    // Assert
+    assert!(result.success, "Call was not successful.");
+    let payload = serde_json::from_value::<Vec<ProcessResult>>(result.payload)
+        .expect("Failed to deserialize result.");
+    assert!(!payload.is_empty(), "Empty results.");
+    assert!(payload.len() <= 3);
+    assert!(payload.windows(2).all(|o| o[0].rss >= o[1].rss));
+}
+
+#[actix_rt::test]
+async fn processes_includes_current_process() {
+    // Arrange
+    let app = spawn_app().await;
+    let message = serde_json::json!({
+        "system": "pc_usage",
+        "task":  "processes",
+        "payload": { "limit": 100000 },
+    })
+    .to_string();
+
+    // Act
+    let result = app.get_first_result(&message).await;
+
+    // Assert
+    assert!(result.success, "Call was not successful.");
+    let payload = serde_json::from_value::<Vec<ProcessResult>>(result.payload)
+        .expect("Failed to deserialize result.");
+    assert!(payload.windows(2).all(|o| o[0].cpu >= o[1].cpu));
+    let current = payload
+        .iter()
+        .find(|o| o.pid == std::process::id())
+        .expect("Current process not found.");
+    assert!(current.rss > 0);
+}
+
+#[actix_rt::test]
+async fn process_receives_usage_by_pid() {
+    // Arrange
+    let app = spawn_app().await;
+    let pid = std::process::id();
+    let message = serde_json::json!({
+        "system": "pc_usage",
+        "task":  "process",
+        "payload": { "pid": pid },
+    })
+    .to_string();
+
+    // Act
+    let result = app.get_first_result(&message).await;
+
+    // Assert
+    assert!(
+        result.success,
+        "Call was not successful: {:?}",
+        result.payload
+    );
+    let payload = serde_json::from_value::<ProcessUsageResult>(result.payload)
+        .expect("Failed to deserialize result.");
+    assert_eq!(pid, payload.pid);
+    assert!(!payload.exited);
+    let usage = payload.usage.expect("Missing usage.");
+    assert!(usage.rss > 0);
+    assert!(usage.threads > 0);
+}
+
+#[actix_rt::test]
+async fn process_receives_usage_by_name() {
+    // Arrange
+    let app = spawn_app().await;
+    let mut child = std::process::Command::new("sleep")
+        .arg("5")
+        .spawn()
+        .expect("Failed to spawn process.");
+    let message = serde_json::json!({
+        "system": "pc_usage",
+        "task":  "process",
+        "payload": { "name": "slee?" },
+    })
+    .to_string();
+
+    // Act
+    let result = app.get_first_result(&message).await;
+    child.kill().unwrap();
+    child.wait().unwrap();
+
+    // Assert
+    assert!(
+        result.success,
+        "Call was not successful: {:?}",
+        result.payload
+    );
+    let payload = serde_json::from_value::<ProcessUsageResult>(result.payload)
+        .expect("Failed to deserialize result.");
+    assert_eq!(Some("sleep"), payload.name.as_deref());
+}
+
+#[actix_rt::test]
+async fn process_receives_error_on_unknown_pid() {
+    // Arrange
+    let app = spawn_app().await;
+    let message = serde_json::json!({
+        "system": "pc_usage",
+        "task":  "process",
+        "payload": { "pid": u32::MAX },
+    })
+    .to_string();
+
+    // Act
+    let result = app.get_first_result(&message).await;
+
+    // Assert
+    assert!(!result.success, "Call should not success.");
+    let payload = serde_json::from_value::<ErrorPayload>(result.payload)
+        .expect("Failed to deserialize error.");
+    assert_eq!("unknown_process", payload.code);
+    assert_eq!(u32::MAX, payload.details["pid"]);
+}
+
+#[actix_rt::test]
+async fn process_subscription_sends_final_message_on_exit() {
+    // Arrange
+    let app = spawn_app().await;
+    let mut connection = app.connect().await;
+    let mut child = std::process::Command::new("sleep")
+        .arg("5")
+        .spawn()
+        .expect("Failed to spawn process.");
+    let message = serde_json::json!({
+        "system": "pc_usage",
+        "task": "subscribe",
+        "payload": { "task": "process", "payload": { "pid": child.id() }, "interval": 200 },
+        "request_id": "subscription"
+    })
+    .to_string();
+    send_message(&mut connection, &message).await;
+    let ack = next_result(&mut connection).await;
+    assert!(
+        ack.success,
+        "Subscription was not successful: {:?}",
+        ack.payload
+    );
+    let first = next_result(&mut connection).await;
+
+    // Act
+    child.kill().unwrap();
+    child.wait().unwrap();
+    let last = loop {
+        let result = next_result(&mut connection).await;
+        let payload = serde_json::from_value::<ProcessUsageResult>(result.payload)
+            .expect("Failed to deserialize result.");
+        if payload.exited {
+            break payload;
+        }
+    };
+    let after_exit = next_result_within(&mut connection, Duration::from_millis(600)).await;
+
+    // Assert
+    let first = serde_json::from_value::<ProcessUsageResult>(first.payload)
+        .expect("Failed to deserialize result.");
+    assert!(!first.exited);
+    assert_eq!(child.id(), last.pid);
+    assert!(last.usage.is_none());
+    assert!(after_exit.is_none(), "Received message after process exit.");
+}
+
+#[actix_rt::test]
+async fn host_info_receives_results() {
+    // Arrange
+    let app = spawn_app().await;
+    let message = serde_json::json!({
+        "system": "pc_usage",
+        "task":  "host_info",
+    })
+    .to_string();
+
+    // Act
+    let result = app.get_first_result(&message).await;
+
+    // Assert
+    assert!(
+        result.success,
+        "Call was not successful: {:?}",
+        result.payload
+    );
+    let payload = serde_json::from_value::<HostInfoResult>(result.payload)
+        .expect("Failed to deserialize result.");
+    assert!(!payload.hostname.is_empty());
+    assert!(!payload.kernel_version.is_empty());
+    assert!(payload.cpu_count > 0);
+    assert!(payload.boot_time > 0);
+    assert!(payload.load_average.one >= 0.0);
+}
+
+#[actix_rt::test]
+async fn cgroup_receives_fixture_usage() {
+    // Arrange
+    let app = spawn_app_with(Arc::new(SystemMetrics), |c| {
+        c.pc_usage.cgroup_root =
+            concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/cgroup/v2").into();
+    })
+    .await;
+    let message = serde_json::json!({
+        "system": "pc_usage",
+        "task":  "cgroup",
+    })
+    .to_string();
+
+    // Act
+    let result = app.get_first_result(&message).await;
+
+    // Assert
+    assert!(
+        result.success,
+        "Call was not successful: {:?}",
+        result.payload
+    );
+    let payload = serde_json::from_value::<CgroupUsage>(result.payload)
+        .expect("Failed to deserialize result.");
+    assert_eq!(CgroupVersion::V2, payload.version);
+    assert_eq!(Some(2.0), payload.cpu.quota);
+    assert_eq!(Some(5_000_000), payload.cpu.usage);
+    assert_eq!(Some(104_857_600), payload.memory.usage);
+    assert_eq!(Some(536_870_912), payload.memory.limit);
+    assert_eq!(Some(1), payload.memory.oom_kills);
+    assert_eq!(Some(12), payload.pids.current);
+    assert_eq!(None, payload.pids.limit);
+}
+
+#[actix_rt::test]
+async fn cgroup_receives_error_without_cgroup_filesystem() {
+    // Arrange
+    let app = spawn_app_with(Arc::new(SystemMetrics), |c| {
+        c.pc_usage.cgroup_root = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures").into();
+    })
+    .await;
+    let message = serde_json::json!({
+        "system": "pc_usage",
+        "task":  "cgroup",
+    })
+    .to_string();
+
+    // Act
+    let result = app.get_first_result(&message).await;
+
+    // Assert
+    assert!(!result.success, "Call should not success.");
+    let payload = serde_json::from_value::<ErrorPayload>(result.payload)
+        .expect("Failed to deserialize error.");
+    assert_eq!("cgroup_unavailable", payload.code);
+}
+
+#[actix_rt::test]
+async fn pressure_receives_fixture_values() {
+    // Arrange
+    let app = spawn_app_with(Arc::new(SystemMetrics), |c| {
+        c.pc_usage.procfs_root = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/proc").into();
+    })
+    .await;
+    let message = serde_json::json!({
+        "system": "pc_usage",
+        "task":  "pressure",
+    })
+    .to_string();
+
+    // Act
+    let result = app.get_first_result(&message).await;
+
+    // Assert
+    assert!(
+        result.success,
+        "Call was not successful: {:?}",
+        result.payload
+    );
+    let payload = serde_json::from_value::<PressureResult>(result.payload)
+        .expect("Failed to deserialize result.");
+    assert_eq!(12.5, payload.cpu.some.avg10);
+    assert_eq!(9876543, payload.cpu.some.total);
+    assert!(payload.cpu.full.is_none());
+    assert_eq!(
+        Some(PressureStall {
+            avg10: 18.75,
+            avg60: 12.0,
+            avg300: 6.5,
+            total: 2345678,
+        }),
+        payload.io.full
+    );
+    assert_eq!(456789, payload.memory.some.total);
+}
+
+#[actix_rt::test]
+async fn pressure_receives_error_without_psi() {
+    // Arrange
+    let app = spawn_app_with(Arc::new(SystemMetrics), |c| {
+        c.pc_usage.procfs_root =
+            concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/cgroup").into();
+    })
+    .await;
+    let message = serde_json::json!({
+        "system": "pc_usage",
+        "task":  "pressure",
+    })
+    .to_string();
+
+    // Act
+    let result = app.get_first_result(&message).await;
+
+    // Assert
+    assert!(!result.success, "Call should not success.");
+    let payload = serde_json::from_value::<ErrorPayload>(result.payload)
+        .expect("Failed to deserialize error.");
+    assert_eq!("psi_unavailable", payload.code);
+}
+
+#[actix_rt::test]
+async fn subscribe_receives_periodic_pressure() {
+    // Arrange
+    let app = spawn_app_with(Arc::new(SystemMetrics), |c| {
+        c.pc_usage.procfs_root = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/proc").into();
+    })
+    .await;
+    let mut connection = app.connect().await;
+    let message = serde_json::json!({
+        "system": "pc_usage",
+        "task": "subscribe",
+        "payload": { "task": "pressure", "interval": 200 },
+        "request_id": "subscription"
+    })
+    .to_string();
+
+    // Act
+    send_message(&mut connection, &message).await;
+    let ack = next_result(&mut connection).await;
+    let first = next_result(&mut connection).await;
+    let second = next_result(&mut connection).await;
+
+    // Assert
+    assert!(ack.success, "Subscription was not successful.");
+    for result in [first, second] {
+        assert!(result.success, "Call was not successful.");
+        assert_eq!(result.request_id.as_deref(), Some("subscription"));
+        serde_json::from_value::<PressureResult>(result.payload)
+            .expect("Failed to deserialize result.");
+    }
+}
+
+#[actix_rt::test]
+async fn history_receives_background_samples() {
+    // Arrange
+    let app = spawn_app().await;
+    let message = serde_json::json!({
+        "system": "pc_usage",
+        "task":  "history",
+    })
+    .to_string();
+    tokio::time::sleep(Duration::from_millis(300)).await;
+
+    // Act
+    let result = app.get_first_result(&message).await;
+
+    // Assert
+    assert!(
+        result.success,
+        "Call was not successful: {:?}",
+        result.payload
+    );
+    let payload = serde_json::from_value::<Vec<HistorySample>>(result.payload)
+        .expect("Failed to deserialize result.");
+    assert!(payload.len() >= 2, "Not enough samples: {:?}", payload);
+    assert!(payload.windows(2).all(|o| o[0].timestamp <= o[1].timestamp));
+    for sample in payload {
+        assert!((0.0..=1.0).contains(&sample.cpu));
+        assert!(sample.memory_used <= sample.memory_total);
+    }
+}
+
+#[actix_rt::test]
+async fn history_receives_scripted_samples() {
+    // Arrange
+    let app = spawn_app_with_metrics(Arc::new(scripted_metrics())).await;
+    let message = serde_json::json!({
+        "system": "pc_usage",
+        "task":  "history",
+    })
+    .to_string();
+    tokio::time::sleep(Duration::from_millis(300)).await;
+
+    // Act
+    let result = app.get_first_result(&message).await;
+
+    // Assert
+    let payload = serde_json::from_value::<Vec<HistorySample>>(result.payload)
+        .expect("Failed to deserialize result.");
+    assert!(payload.len() >= 2, "Not enough samples: {:?}", payload);
+    for sample in payload {
+        assert_eq!(0.625, sample.cpu);
+        assert_eq!(0.375, sample.cpu_user);
+        assert_eq!(4 * GIB, sample.memory_used);
+        assert_eq!(8 * GIB, sample.memory_total);
+    }
+}
+
+#[actix_rt::test]
+async fn history_filters_and_downsamples() {
+    // Arrange
+    let app = spawn_app().await;
+    tokio::time::sleep(Duration::from_millis(400)).await;
+    let all = serde_json::json!({
+        "system": "pc_usage",
+        "task":  "history",
+    })
+    .to_string();
+    let all = app.get_first_result(&all).await;
+    let all = serde_json::from_value::<Vec<HistorySample>>(all.payload).unwrap();
+    let from = all[1].timestamp;
+    let message = serde_json::json!({
+        "system": "pc_usage",
+        "task":  "history",
+        "payload": { "from": from, "max_points": 2 },
+    })
+    .to_string();
+
+    // Act
+    let result = app.get_first_result(&message).await;
+
+    // Assert
+    assert!(
+        result.success,
+        "Call was not successful: {:?}",
+        result.payload
+    );
+    let payload = serde_json::from_value::<Vec<HistorySample>>(result.payload)
+        .expect("Failed to deserialize result.");
+    assert_eq!(2, payload.len(), "{:?}", payload);
+    assert!(payload.iter().all(|o| o.timestamp >= from));
+}
+
+#[actix_rt::test]
+async fn alert_pushes_event_when_firing() {
+    // Arrange
+    let app = spawn_app().await;
+    let mut connection = app.connect().await;
+    let message = serde_json::json!({
+        "system": "pc_usage",
+        "task": "add_alert",
+        "payload": { "metric": "cpu", "condition": "above", "threshold": -1.0 },
+        "request_id": "alert"
+    })
+    .to_string();
+
+    // Act
+    send_message(&mut connection, &message).await;
+    let ack = next_result(&mut connection).await;
+    let event = next_result(&mut connection).await;
+
+    // Assert
+    assert!(ack.success, "Call was not successful: {:?}", ack.payload);
+    let info =
+        serde_json::from_value::<AlertInfo>(ack.payload).expect("Failed to deserialize result.");
+    assert_eq!(Some("alert".to_string()), event.request_id);
+    let event =
+        serde_json::from_value::<AlertEvent>(event.payload).expect("Failed to deserialize event.");
+    assert_eq!(info.id, event.alert_id);
+    assert_eq!(AlertState::Firing, event.state);
+}
+
+#[actix_rt::test]
+async fn alert_fires_and_resolves_on_scripted_load() {
+    // Arrange
+    let metrics = scripted_metrics().cpu_loads(vec![
+        vec![core(0.25, 0.0, 0.75)],
+        vec![core(0.25, 0.0, 0.75)],
+        vec![core(0.25, 0.0, 0.75)],
+        vec![core(0.75, 0.0, 0.25)],
+        vec![core(0.75, 0.0, 0.25)],
+        vec![core(0.5, 0.0, 0.5)],
+    ]);
+    let app = spawn_app_with_metrics(Arc::new(metrics)).await;
+    let mut connection = app.connect().await;
+    let message = serde_json::json!({
+        "system": "pc_usage",
+        "task": "add_alert",
+        "payload": { "metric": "cpu_user", "condition": "above", "threshold": 0.5 },
+        "request_id": "alert"
+    })
+    .to_string();
+    send_message(&mut connection, &message).await;
+    next_result(&mut connection).await;
+
+    // Act
+    let fired = next_result(&mut connection).await;
+    let resolved = next_result(&mut connection).await;
+
+    // Assert
+    let fired =
+        serde_json::from_value::<AlertEvent>(fired.payload).expect("Failed to deserialize event.");
+    assert_eq!(AlertState::Firing, fired.state);
+    assert_eq!(0.75, fired.value);
+    let resolved = serde_json::from_value::<AlertEvent>(resolved.payload)
+        .expect("Failed to deserialize event.");
+    assert_eq!(AlertState::Resolved, resolved.state);
+    assert_eq!(0.5, resolved.value);
+}
+
+#[actix_rt::test]
+async fn alerts_can_be_listed_and_removed() {
+    // Arrange
+    let app = spawn_app().await;
+    let mut connection = app.connect().await;
+    let message = serde_json::json!({
+        "system": "pc_usage",
+        "task": "add_alert",
+        "payload": { "metric": "memory_used", "condition": "below", "threshold": 0.0 },
+    })
+    .to_string();
+    send_message(&mut connection, &message).await;
+    let info = next_result(&mut connection).await;
+    let info =
+        serde_json::from_value::<AlertInfo>(info.payload).expect("Failed to deserialize result.");
+    let list = serde_json::json!({
+        "system": "pc_usage",
+        "task": "list_alerts",
+    })
+    .to_string();
+    let remove = serde_json::json!({
+        "system": "pc_usage",
+        "task": "remove_alert",
+        "payload": { "id": info.id },
+    })
+    .to_string();
+
+    // Act
+    send_message(&mut connection, &list).await;
+    let listed = next_result(&mut connection).await;
+    send_message(&mut connection, &remove).await;
+    let removed = next_result(&mut connection).await;
+    send_message(&mut connection, &list).await;
+    let listed_after = next_result(&mut connection).await;
+
+    // Assert
+    let listed = serde_json::from_value::<Vec<AlertInfo>>(listed.payload)
+        .expect("Failed to deserialize result.");
+    assert_eq!(vec![info.clone()], listed);
+    assert!(
+        removed.success,
+        "Call was not successful: {:?}",
+        removed.payload
+    );
+    assert_eq!(info.id, removed.payload["removed"]);
+    let listed_after = serde_json::from_value::<Vec<AlertInfo>>(listed_after.payload)
+        .expect("Failed to deserialize result.");
+    assert!(listed_after.is_empty());
+}
+
+#[actix_rt::test]
+async fn sampler_goes_idle_once_alerts_are_removed() {
+    // Arrange
+    let app = spawn_app_with(Arc::new(SystemMetrics), |c| {
+        c.pc_usage.history_size = 0;
+    })
+    .await;
+    let mut connection = app.connect().await;
+    let add = serde_json::json!({
+        "system": "pc_usage",
+        "task": "add_alert",
+        "payload": { "metric": "memory_used", "condition": "below", "threshold": 0.0 },
+    })
+    .to_string();
+    send_message(&mut connection, &add).await;
+    let info = next_result(&mut connection).await;
+    let info =
+        serde_json::from_value::<AlertInfo>(info.payload).expect("Failed to deserialize result.");
+    next_result_within(&mut connection, Duration::from_millis(150)).await;
+    let sampling = app.get_metrics().await;
+    let remove = |id: u64| {
+        serde_json::json!({
+            "system": "pc_usage",
+            "task": "remove_alert",
+            "payload": { "id": id },
+        })
+        .to_string()
+    };
+
+    // Act
+    send_message(&mut connection, &remove(info.id)).await;
+    next_result(&mut connection).await;
+    send_message(&mut connection, &remove(info.id)).await;
+    next_result(&mut connection).await;
+    next_result_within(&mut connection, Duration::from_millis(150)).await;
+    let idle = app.get_metrics().await;
+
+    // Assert
+    assert!(sampling.contains("host_cpu_usage_ratio"), "{}", sampling);
+    assert!(idle.contains("host_memory_total_bytes"), "{}", idle);
+    assert!(!idle.contains("host_cpu_usage_ratio"), "{}", idle);
+}
+
+#[actix_rt::test]
+async fn remove_alert_receives_error_on_unknown_id() {
+    // Arrange
+    let app = spawn_app().await;
+    let message = serde_json::json!({
+        "system": "pc_usage",
+        "task": "remove_alert",
+        "payload": { "id": 12345 },
+    })
+    .to_string();
+
+    // Act
+    let result = app.get_first_result(&message).await;
+
+    // Assert
+    assert!(!result.success, "Call should not success.");
+    let payload = serde_json::from_value::<ErrorPayload>(result.payload)
+        .expect("Failed to deserialize error.");
+    assert_eq!("unknown_alert", payload.code);
+    assert_eq!(12345, payload.details["id"]);
 }
 
 #[actix_rt::test]
@@ -37,6 +1101,345 @@ async fn receive_error_on_invalid_task_name() {
     let result = app.get_first_result(&message).await;
 
     // Assert
-    assert_eq!(result.system.unwrap(), WebsocketSystems::PcUsage);
+    assert_eq!(result.system.unwrap(), "pc_usage");
+    assert!(!result.success, "Call should not success.");
+}
+
+#[actix_rt::test]
+async fn subscribe_receives_periodic_cpu_load() {
+    // Arrange
+    let app = spawn_app().await;
+    let mut connection = app.connect().await;
+    let message = serde_json::json!({
+        "system": "pc_usage",
+        "task": "subscribe",
+        "payload": { "task": "cpu_load", "interval": 200 },
+        "request_id": "subscription"
+    })
+    .to_string();
+
+    // Act
+    send_message(&mut connection, &message).await;
+    let ack = next_result(&mut connection).await;
+    let first = next_result(&mut connection).await;
+    let second = next_result(&mut connection).await;
+
+    // Assert
+    assert!(ack.success, "Subscription was not successful.");
+    for result in [first, second] {
+        assert!(result.success, "Call was not successful.");
+        assert_eq!(result.request_id.as_deref(), Some("subscription"));
+        let payload = serde_json::from_value::<Vec<CpuLoadResult>>(result.payload)
+            .expect("Failed to deserialize result.");
+        assert!(!payload.is_empty(), "Empty results.");
+    }
+}
+
+#[actix_rt::test]
+async fn unsubscribe_stops_cpu_load_messages() {
+    // Arrange
+    let app = spawn_app().await;
+    let mut connection = app.connect().await;
+    let subscribe = serde_json::json!({
+        "system": "pc_usage",
+        "task": "subscribe",
+        "payload": { "task": "cpu_load", "interval": 200 },
+    })
+    .to_string();
+    let unsubscribe = serde_json::json!({
+        "system": "pc_usage",
+        "task": "unsubscribe",
+        "payload": { "task": "cpu_load" },
+        "request_id": "unsubscribe"
+    })
+    .to_string();
+    send_message(&mut connection, &subscribe).await;
+    next_result(&mut connection).await;
+
+    // Act
+    send_message(&mut connection, &unsubscribe).await;
+    loop {
+        let result = next_result(&mut connection).await;
+        if result.request_id.as_deref() == Some("unsubscribe") {
+            assert!(result.success, "Unsubscribe was not successful.");
+            break;
+        }
+    }
+    let result = next_result_within(&mut connection, Duration::from_millis(600)).await;
+
+    // Assert
+    assert!(result.is_none(), "Received message after unsubscribing.");
+}
+
+#[actix_rt::test]
+async fn subscription_skips_intervals_while_measuring() {
+    // Arrange
+    let app = spawn_app().await;
+    let mut connection = app.connect().await;
+    // The processes window (500ms) is longer than the interval
+    let message = serde_json::json!({
+        "system": "pc_usage",
+        "task": "subscribe",
+        "payload": { "task": "processes", "interval": 200 },
+        "request_id": "subscription"
+    })
+    .to_string();
+
+    // Act
+    send_message(&mut connection, &message).await;
+    let ack = next_result(&mut connection).await;
+    let _first = next_result(&mut connection).await;
+    let start = Instant::now();
+    let second = next_result(&mut connection).await;
+    let elapsed = start.elapsed();
+
+    // Assert
+    assert!(ack.success, "Subscription was not successful.");
+    assert!(second.success, "Call was not successful.");
+    assert!(
+        elapsed >= Duration::from_millis(300),
+        "Received duplicated results {:?} apart.",
+        elapsed
+    );
+}
+
+#[actix_rt::test]
+async fn process_subscription_skips_intervals_while_measuring() {
+    // Arrange
+    let app = spawn_app().await;
+    let mut connection = app.connect().await;
+    // The process window (500ms) is longer than the interval
+    let message = serde_json::json!({
+        "system": "pc_usage",
+        "task": "subscribe",
+        "payload": { "task": "process", "payload": { "pid": std::process::id() }, "interval": 200 },
+        "request_id": "subscription"
+    })
+    .to_string();
+
+    // Act
+    send_message(&mut connection, &message).await;
+    let ack = next_result(&mut connection).await;
+    let _first = next_result(&mut connection).await;
+    let start = Instant::now();
+    let second = next_result(&mut connection).await;
+    let elapsed = start.elapsed();
+
+    // Assert
+    assert!(ack.success, "Subscription was not successful.");
+    assert!(second.success, "Call was not successful.");
+    assert!(
+        elapsed >= Duration::from_millis(300),
+        "Received overlapping results {:?} apart.",
+        elapsed
+    );
+}
+
+#[actix_rt::test]
+async fn subscriptions_to_the_same_task_share_measurements() {
+    // Arrange
+    let app = spawn_app().await;
+    let mut first = app.connect().await;
+    let mut second = app.connect().await;
+    let subscribe = |request_id: &str| {
+        serde_json::json!({
+            "system": "pc_usage",
+            "task": "subscribe",
+            "payload": { "task": "cpu_load", "interval": 200 },
+            "request_id": request_id
+        })
+        .to_string()
+    };
+    send_message(&mut first, &subscribe("first")).await;
+    next_result(&mut first).await;
+    send_message(&mut second, &subscribe("second")).await;
+    next_result(&mut second).await;
+
+    // Act
+    // Both connections are read at once, so neither misses its heartbeat
+    let mut first_results = Vec::new();
+    let mut second_results = Vec::new();
+    for _ in 0..3 {
+        let (a, b) = tokio::join!(next_result(&mut first), next_result(&mut second));
+        first_results.push(a);
+        second_results.push(b);
+    }
+
+    // Assert
+    assert!(first_results
+        .iter()
+        .all(|o| o.request_id.as_deref() == Some("first")));
+    assert!(second_results
+        .iter()
+        .all(|o| o.request_id.as_deref() == Some("second")));
+    let shared = second_results
+        .iter()
+        .filter(|b| first_results.iter().any(|a| a.payload == b.payload))
+        .count();
+    assert!(
+        shared >= 2,
+        "Subscribers did not receive the same measurements."
+    );
+}
+
+#[actix_rt::test]
+async fn unsubscribe_keeps_other_subscribers_streaming() {
+    // Arrange
+    let app = spawn_app().await;
+    let mut first = app.connect().await;
+    let mut second = app.connect().await;
+    let subscribe = serde_json::json!({
+        "system": "pc_usage",
+        "task": "subscribe",
+        "payload": { "task": "memory", "interval": 200 },
+    })
+    .to_string();
+    let unsubscribe = serde_json::json!({
+        "system": "pc_usage",
+        "task": "unsubscribe",
+        "payload": { "task": "memory" },
+        "request_id": "unsubscribe"
+    })
+    .to_string();
+    send_message(&mut first, &subscribe).await;
+    next_result(&mut first).await;
+    send_message(&mut second, &subscribe).await;
+    next_result(&mut second).await;
+
+    // Act
+    send_message(&mut first, &unsubscribe).await;
+    loop {
+        let result = next_result(&mut first).await;
+        if result.request_id.as_deref() == Some("unsubscribe") {
+            break;
+        }
+    }
+    let (after_unsubscribe, results) = tokio::join!(
+        next_result_within(&mut first, Duration::from_millis(600)),
+        async {
+            let mut results = Vec::new();
+            for _ in 0..2 {
+                results.push(next_result(&mut second).await);
+            }
+            results
+        }
+    );
+
+    // Assert
+    assert!(
+        after_unsubscribe.is_none(),
+        "Received message after unsubscribing."
+    );
+    for result in results {
+        assert!(result.success, "Call was not successful.");
+        serde_json::from_value::<MemoryResult>(result.payload)
+            .expect("Failed to deserialize result.");
+    }
+}
+
+#[actix_rt::test]
+async fn subscribe_receives_error_on_invalid_payload() {
+    // Arrange
+    let app = spawn_app().await;
+    let messages = [
+        serde_json::json!({ "task": "cpu_load" }),
+        serde_json::json!({ "task": "cpu_load", "interval": 1 }),
+        serde_json::json!({ "task": "unsubscribe", "payload": { "task": "cpu_load" }, "interval": 1000 }),
+        serde_json::json!({ "task": "invalid_task_name", "interval": 1000 }),
+    ];
+
+    for payload in messages {
+        let message = serde_json::json!({
+            "system": "pc_usage",
+            "task": "subscribe",
+            "payload": payload,
+        })
+        .to_string();
+
+        // Act
+        let result = app.get_first_result(&message).await;
+
+        // Assert
+        assert!(!result.success, "Call should not success: {:?}", payload);
+    }
+}
+
+#[actix_rt::test]
+async fn unsubscribe_receives_error_without_subscription() {
+    // Arrange
+    let app = spawn_app().await;
+    let message = serde_json::json!({
+        "system": "pc_usage",
+        "task": "unsubscribe",
+        "payload": { "task": "cpu_load" },
+    })
+    .to_string();
+
+    // Act
+    let result = app.get_first_result(&message).await;
+
+    // Assert
     assert!(!result.success, "Call should not success.");
 }
+
+#[actix_rt::test]
+async fn concurrent_cpu_load_requests_share_measurement() {
+    // Arrange
+    let app = spawn_app().await;
+    let mut connection = app.connect().await;
+    let n = 5;
+
+    // Act
+    let start = Instant::now();
+    for i in 0..n {
+        let message = serde_json::json!({
+            "system": "pc_usage",
+            "task": "cpu_load",
+            "request_id": i.to_string(),
+        })
+        .to_string();
+        send_message(&mut connection, &message).await;
+    }
+    let mut request_ids = Vec::new();
+    for _ in 0..n {
+        let result = next_result(&mut connection).await;
+        assert!(result.success, "Call was not successful.");
+        request_ids.push(result.request_id.unwrap());
+    }
+    let elapsed = start.elapsed();
+
+    // Assert
+    request_ids.sort();
+    assert_eq!(request_ids, ["0", "1", "2", "3", "4"]);
+    assert!(
+        elapsed < Duration::from_millis(200 * n),
+        "Requests were not served concurrently: {:?}",
+        elapsed
+    );
+}
+
+#[actix_rt::test]
+async fn cpu_load_does_not_block_other_tasks() {
+    // Arrange
+    let app = spawn_app().await;
+    let mut connection = app.connect().await;
+    let cpu_load = serde_json::json!({
+        "system": "pc_usage",
+        "task": "cpu_load",
+    })
+    .to_string();
+    let get_files = serde_json::json!({
+        "system": "python_repo",
+        "task": "get_files",
+        "payload": { "path": "tests/examples" },
+    })
+    .to_string();
+
+    // Act
+    send_message(&mut connection, &cpu_load).await;
+    send_message(&mut connection, &get_files).await;
+    let result = next_result(&mut connection).await;
+
+    // Assert
+    assert_eq!(result.system.unwrap(), "python_repo");
+}
@@ -1,5 +1,4 @@
 use crate::helpers::spawn_app;
-use actix_websockets::websocket::message::WebsocketSystems;
 
 #[actix_rt::test]
 async fn get_files_receive_python_files_on_valid_path() {
@@ -17,7 +16,7 @@ async fn get_files_receive_python_files_on_valid_path() {
     let result = app.get_first_result(&message).await;
 
     // Assert
-    assert_eq!(result.system.unwrap(), WebsocketSystems::PythonRepo);
+    assert_eq!(result.system.unwrap(), "python_repo");
     assert!(result.success, "Call was not successful.");
     let payload = result.payload.to_string();
     assert!(
@@ -26,6 +25,32 @@ async fn get_files_receive_python_files_on_valid_path() {
     );
 }
 
+#[actix_rt::test]
+async fn get_files_accepts_path_object_payload() {
+    // Arrange
+    let app = spawn_app().await;
+    let message = serde_json::json!({
+        "system": "python_repo",
+        "task": "get_files",
+        "payload": { "path": "tests/examples" }
+    })
+    .to_string();
+
+    // Act
+    let result = app.get_first_result(&message).await;
+
+    // Assert
+    assert!(
+        result.success,
+        "Call was not successful: {:?}",
+        result.payload
+    );
+    assert!(
+        result.payload.to_string().contains("a.py"),
+        "Expected file (a.py) not found in payload."
+    );
+}
+
 #[actix_rt::test]
 async fn get_files_receive_error_on_invalid_path() {
     // Arrange
@@ -41,7 +66,7 @@ async fn get_files_receive_error_on_invalid_path() {
     let result = app.get_first_result(&message).await;
 
     // Assert
-    assert_eq!(result.system.unwrap(), WebsocketSystems::PythonRepo);
+    assert_eq!(result.system.unwrap(), "python_repo");
     assert!(!result.success, "Call should not success.");
 }
 
@@ -59,6 +84,46 @@ async fn receive_error_on_invalid_task_name() {
     let result = app.get_first_result(&message).await;
 
     // Assert
-    assert_eq!(result.system.unwrap(), WebsocketSystems::PythonRepo);
+    assert_eq!(result.system.unwrap(), "python_repo");
     assert!(!result.success, "Call should not success.");
 }
+
+#[actix_rt::test]
+async fn get_files_receive_error_naming_missing_field() {
+    // Arrange
+    let app = spawn_app().await;
+    let invalid_payloads = [
+        serde_json::json!({}),
+        serde_json::json!({ "path": 1 }),
+        serde_json::json!(1),
+    ];
+
+    for payload in invalid_payloads {
+        let message = serde_json::json!({
+            "system": "python_repo",
+            "task": "get_files",
+            "payload": payload,
+        })
+        .to_string();
+
+        // Act
+        let result = app.get_first_result(&message).await;
+
+        // Assert
+        assert_eq!(result.system.unwrap(), "python_repo");
+        assert!(!result.success, "Call should not success: {:?}", payload);
+    }
+
+    let message = serde_json::json!({
+        "system": "python_repo",
+        "task": "get_files",
+        "payload": {},
+    })
+    .to_string();
+    let result = app.get_first_result(&message).await;
+    assert!(
+        result.payload.to_string().contains("`path`"),
+        "Missing field not named in error: {}",
+        result.payload
+    );
+}
@@ -0,0 +1,56 @@
+use crate::helpers::spawn_app;
+use actix::Recipient;
+use actix_websockets::websocket::message::SessionCount;
+use std::time::{Duration, Instant};
+
+/// Polls `addr` until its session count is `expected` or a timeout is reached.
+async fn wait_for_session_count(addr: &Recipient<SessionCount>, expected: usize) -> usize {
+    let start = Instant::now();
+    loop {
+        let count = addr
+            .send(SessionCount)
+            .await
+            .expect("Failed to get session count.");
+        if count == expected || start.elapsed() > Duration::from_secs(2) {
+            return count;
+        }
+        tokio::time::sleep(Duration::from_millis(25)).await;
+    }
+}
+
+#[actix_rt::test]
+async fn sessions_are_removed_after_clients_disconnect() {
+    // Arrange
+    let app = spawn_app().await;
+    let python_repo = &app.registry.get("python_repo").unwrap().session_count;
+    let pc_usage = &app.registry.get("pc_usage").unwrap().session_count;
+    let mut connections = Vec::new();
+    for _ in 0..3 {
+        connections.push(app.connect().await);
+    }
+    assert_eq!(wait_for_session_count(python_repo, 3).await, 3);
+    assert_eq!(wait_for_session_count(pc_usage, 3).await, 3);
+
+    // Act
+    drop(connections);
+
+    // Assert
+    assert_eq!(wait_for_session_count(python_repo, 0).await, 0);
+    assert_eq!(wait_for_session_count(pc_usage, 0).await, 0);
+}
+
+#[actix_rt::test]
+async fn only_closed_sessions_are_removed() {
+    // Arrange
+    let app = spawn_app().await;
+    let pc_usage = &app.registry.get("pc_usage").unwrap().session_count;
+    let _connection = app.connect().await;
+    let closed_connection = app.connect().await;
+    assert_eq!(wait_for_session_count(pc_usage, 2).await, 2);
+
+    // Act
+    drop(closed_connection);
+
+    // Assert
+    assert_eq!(wait_for_session_count(pc_usage, 1).await, 1);
+}